@@ -1,3 +1,4 @@
+use rn2903::boards::LoStik;
 use rn2903::Rn2903;
 use std::env::args;
 use std::process::exit;
@@ -8,8 +9,7 @@ fn main() {
     let args: Vec<_> = args().collect();
     if args.len() <= 1 {
         eprintln!("rn2903_blinky <serial port>");
-        eprintln!("\tReset the module and toggle pin 0b10 on and off.");
-        eprintln!("\tThis corresponds to the blue user LED on the LoStik.");
+        eprintln!("\tReset the module and toggle the blue LED of a LoStik on and off.");
         exit(1);
     }
 
@@ -22,11 +22,12 @@ fn main() {
 
     txvr.system_module_reset().unwrap();
     txvr.transact(b"mac pause").unwrap();
+    let mut stik = LoStik::new(txvr).unwrap();
 
     loop {
-        txvr.transact(b"sys set pindig GPIO10 1").unwrap();
+        stik.rx_led(true).unwrap();
         thread::sleep(Duration::from_millis(1000));
-        txvr.transact(b"sys set pindig GPIO10 0").unwrap();
+        stik.rx_led(false).unwrap();
         thread::sleep(Duration::from_millis(1000));
     }
 }
@@ -1,8 +1,6 @@
-use rn2903::{bytes_to_string, Rn2903};
+use rn2903::Rn2903;
 use std::env::args;
 use std::process::exit;
-use std::thread;
-use std::time::Duration;
 
 fn main() {
     let args: Vec<_> = args().collect();
@@ -20,13 +18,8 @@ fn main() {
     );
 
     txvr.mac_pause().unwrap();
-    txvr.transact(b"sys set pindig GPIO10 0").unwrap();
-    loop {
-        if let Some(packet) = txvr.radio_rx(65535).unwrap() {
-            println!("{:?}", packet);
-            txvr.transact(b"sys set pindig GPIO10 1").unwrap();
-            thread::sleep(Duration::from_millis(100));
-            txvr.transact(b"sys set pindig GPIO10 0").unwrap();
-        }
+    for packet in txvr.receive_iter() {
+        let packet = packet.unwrap();
+        println!("{:?} (SNR {:?} dB)", packet.payload, packet.snr);
     }
 }
@@ -1,8 +1,7 @@
+use rn2903::boards::LoStik;
 use rn2903::{NvmAddress, Rn2903};
 use std::env::args;
 use std::process::exit;
-use std::thread;
-use std::time::Duration;
 
 fn main() {
     let args: Vec<_> = args().collect();
@@ -21,25 +20,26 @@ fn main() {
 
     txvr.system_module_reset().unwrap();
     txvr.transact(b"mac pause").unwrap();
+    let mut stik = LoStik::new(txvr).unwrap();
     let addr = NvmAddress::new(0x300);
 
-    txvr.transact(b"sys set pindig GPIO10 1").unwrap();
-    let prev = txvr.system_get_nvm(addr).unwrap();
+    stik.rx_led(true).unwrap();
+    let prev = stik.txvr().system_get_nvm(addr).unwrap();
     println!("Previous value: {:#x}", prev);
-    txvr.transact(b"sys set pindig GPIO10 0").unwrap();
+    stik.rx_led(false).unwrap();
 
-    txvr.transact(b"sys set pindig GPIO11 1").unwrap();
-    txvr.system_set_nvm(addr, 0xAB).unwrap();
+    stik.tx_led(true).unwrap();
+    stik.txvr().system_set_nvm(addr, 0xAB).unwrap();
     println!("Wrote new value");
-    txvr.transact(b"sys set pindig GPIO11 0").unwrap();
+    stik.tx_led(false).unwrap();
 
-    txvr.transact(b"sys set pindig GPIO10 1").unwrap();
-    let new = txvr.system_get_nvm(addr).unwrap();
+    stik.rx_led(true).unwrap();
+    let new = stik.txvr().system_get_nvm(addr).unwrap();
     println!("New value: {:#x}", new);
-    txvr.transact(b"sys set pindig GPIO10 0").unwrap();
+    stik.rx_led(false).unwrap();
 
-    txvr.transact(b"sys set pindig GPIO11 1").unwrap();
-    txvr.system_set_nvm(addr, prev).unwrap();
+    stik.tx_led(true).unwrap();
+    stik.txvr().system_set_nvm(addr, prev).unwrap();
     println!("Restored old value");
-    txvr.transact(b"sys set pindig GPIO11 0").unwrap();
+    stik.tx_led(false).unwrap();
 }
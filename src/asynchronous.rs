@@ -0,0 +1,910 @@
+//! An asynchronous front-end for the module, for use with Tokio.
+
+use crate::framing::LineBuffer;
+use crate::operation::{self, Flow, Operation, Session, Step};
+use crate::protocol::{self, Request};
+use crate::{
+    bytes_to_string, AesKey, Bandwidth, CodingRate, DevAddr, Downlink, Error, Eui64,
+    FirmwareVersion, FskBandwidth, JoinMode, Model, ModulationMode, NvmAddress, NvmWriteOptions,
+    Pin, PinMode, ReceivedPacket, Result, SpreadingFactor, Transmission, DEFAULT_RESPONSE_TIMEOUT,
+};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::time::{self, Instant};
+
+/// A handle to a module reached over an asynchronous byte stream, such as a
+/// `tokio_serial::SerialStream`, a `tokio::net::TcpStream` connected to a serial bridge,
+/// or anything else which implements `AsyncRead` and `AsyncWrite`.
+///
+/// This offers the same system, radio, and MAC functions as
+/// [`Rn2903`](struct.Rn2903.html), returning futures instead of blocking. Commands are
+/// formatted and replies interpreted exactly as they are by `Rn2903`, and the same
+/// validation is applied before anything is sent. Timeouts are measured with Tokio's
+/// clock, so a runtime with the time driver enabled is required.
+///
+/// Only available with the `async` feature.
+///
+/// # Example
+///
+/// ```
+/// # use rn2903::{AsyncRn2903, SpreadingFactor};
+/// # use rn2903::sim::Simulator;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut sim = Simulator::new();
+/// sim.queue_packet(b"hello");
+/// let mut txvr = AsyncRn2903::new(sim).await.unwrap();
+///
+/// txvr.mac_pause().await.unwrap();
+/// txvr.radio_set_spreading_factor(SpreadingFactor::Sf8).await.unwrap();
+/// let packet = txvr.radio_receive(100).await.unwrap().expect("a packet");
+/// assert_eq!(packet.payload, b"hello");
+/// assert_eq!(packet.spreading_factor, Some(SpreadingFactor::Sf8));
+/// # }
+/// ```
+pub struct AsyncRn2903<T> {
+    port: T,
+    input: LineBuffer,
+    last_command: Vec<u8>,
+    session: Session,
+    response_timeout: Duration,
+    write_delay: Option<Duration>,
+}
+
+/// # Meta (type) Functions
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Open a new connection to a module over the given stream, checking that it is an
+    /// RN2903 or RN2483 with `sys get ver`.
+    pub async fn new(port: T) -> Result<Self> {
+        let mut new = Self::new_unchecked(port);
+        new.system_firmware_version().await?;
+        Ok(new)
+    }
+
+    /// Open a new connection to a module over the given stream without performing a
+    /// `sys get ver` check.
+    pub fn new_unchecked(port: T) -> Self {
+        Self {
+            port,
+            input: LineBuffer::default(),
+            last_command: Vec::new(),
+            session: Session::default(),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            write_delay: None,
+        }
+    }
+
+    /// Returns the model of the connected module, if it has been detected.
+    pub fn model(&self) -> Option<Model> {
+        self.session.model
+    }
+
+    /// Acquires temporary direct access to the captured stream.
+    pub fn port(&mut self) -> &mut T {
+        &mut self.port
+    }
+
+    /// Consumes the `AsyncRn2903`, returning the captured stream.
+    pub fn into_port(self) -> T {
+        self.port
+    }
+
+    /// Returns the amount of time to wait for the module to answer a command.
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    /// Sets the amount of time to wait for the module to answer a command, as
+    /// [`Rn2903::set_response_timeout()`](struct.Rn2903.html#method.set_response_timeout)
+    /// does.
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    /// Returns the delay inserted after writing each command, if any.
+    pub fn write_delay(&self) -> Option<Duration> {
+        self.write_delay
+    }
+
+    /// Sets a delay to be inserted after writing each command, as
+    /// [`Rn2903::set_write_delay()`](struct.Rn2903.html#method.set_write_delay) does.
+    pub fn set_write_delay(&mut self, delay: Option<Duration>) {
+        self.write_delay = delay;
+    }
+}
+
+/// # Low-level Communications
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Writes the specified command to the module and returns a single line in response.
+    pub async fn transact(&mut self, command: &[u8]) -> Result<Vec<u8>> {
+        self.send_line(command).await?;
+        self.read_line().await
+    }
+
+    /// Writes the specified command to the module and returns a single line in response,
+    /// waiting up to the given amount of time for it instead of the response timeout.
+    pub async fn transact_with_timeout(
+        &mut self,
+        command: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        self.send_line(command).await?;
+        self.read_line_until(Some(Instant::now() + timeout)).await
+    }
+
+    /// Sends the command of the given request and interprets the reply.
+    async fn request<V>(&mut self, request: Request<V>) -> Result<V> {
+        self.run(operation::Single::new(request)).await
+    }
+
+    /// Performs the steps of the given operation until it is done.
+    async fn run<O: Operation>(&mut self, mut operation: O) -> Result<O::Output> {
+        let mut reply = None;
+        loop {
+            match operation.resume(&mut self.session, reply)? {
+                Flow::Step(step) => reply = self.perform(step).await?,
+                Flow::Done(output) => return Ok(output),
+            }
+        }
+    }
+
+    /// Performs a single step of an operation, returning the line it read, if any.
+    async fn perform(&mut self, step: Step) -> Result<Option<Vec<u8>>> {
+        match step {
+            Step::Transact(line) => {
+                self.write_line(line.as_bytes()).await?;
+                self.read_line().await.map(Some)
+            }
+            Step::Write(line) => self.write_line(line.as_bytes()).await.map(|()| None),
+            Step::ReadLine(wait) => {
+                let deadline = wait.map(|wait| Instant::now() + self.response_timeout + wait);
+                self.read_line_until(deadline).await.map(Some)
+            }
+            Step::Poll(wait) => self.poll_line(wait).await,
+            Step::WriteBytes(bytes) => {
+                self.port.write_all(&bytes).await?;
+                self.port.flush().await?;
+                Ok(None)
+            }
+            // Only streams which implement `AsyncBreak` can send one; see `system_wake`.
+            Step::Break => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "this stream cannot send a break",
+            )
+            .into()),
+            Step::Drain(quiet) => self.drain(quiet).await.map(|()| None),
+        }
+    }
+
+    /// Discards everything received so far, and anything more until nothing arrives for
+    /// the given time, or the response timeout passes.
+    async fn drain(&mut self, quiet: Duration) -> Result<()> {
+        let deadline = Instant::now() + self.response_timeout;
+        let mut buf = [0; 64];
+        loop {
+            self.input = LineBuffer::default();
+            let until = (Instant::now() + quiet).min(deadline);
+            match time::timeout_at(until, self.port.read(&mut buf)).await {
+                Err(_) => return Ok(()),
+                Ok(Ok(0)) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(Ok(_)) => (),
+                Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => (),
+                Ok(Err(e)) => return Err(e.into()),
+            }
+            if Instant::now() >= deadline {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Writes the specified command to the module, adding a CRLF and flushing the stream.
+    /// Radio settings the handle remembers are forgotten, as the command may change them.
+    pub async fn send_line(&mut self, line: &[u8]) -> Result<()> {
+        self.session.radio = Default::default();
+        self.write_line(line).await
+    }
+
+    /// Writes a command line built by the crate, whose effect on the radio settings is
+    /// accounted for by the operation sending it.
+    async fn write_line(&mut self, line: &[u8]) -> Result<()> {
+        let bytes: Vec<u8> = line.iter().chain(b"\x0D\x0A".iter()).cloned().collect();
+        self.last_command = line.to_vec();
+        self.port.write_all(&bytes).await?;
+        self.port.flush().await?;
+        if let Some(delay) = self.write_delay {
+            time::sleep(delay).await;
+        }
+        Ok(())
+    }
+
+    /// Reads a line from the module, not including the CRLF, waiting at most the
+    /// response timeout for it to arrive.
+    pub async fn read_line(&mut self) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + self.response_timeout;
+        self.read_line_until(Some(deadline)).await
+    }
+
+    /// Waits up to the given amount of time for a line from the module, returning
+    /// `Ok(None)` if no complete line arrives in time.
+    pub async fn poll_line(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>> {
+        match self.read_line_until(Some(Instant::now() + timeout)).await {
+            Ok(line) => Ok(Some(line)),
+            Err(Error::Timeout { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a line, giving up at the given deadline, or waiting forever if there is
+    /// none. Partial lines are kept if the deadline passes.
+    async fn read_line_until(&mut self, deadline: Option<Instant>) -> Result<Vec<u8>> {
+        let mut buf = [0; 64];
+        loop {
+            if let Some(line) = self.input.next_line() {
+                return Ok(line);
+            }
+            let read = match deadline {
+                Some(deadline) => {
+                    match time::timeout_at(deadline, self.port.read(&mut buf)).await {
+                        Ok(read) => read,
+                        Err(_) => {
+                            return Err(Error::Timeout {
+                                command: bytes_to_string(&self.last_command),
+                            })
+                        }
+                    }
+                }
+                None => self.port.read(&mut buf).await,
+            };
+            match read {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => self.input.extend(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// # System API Functions
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Queries the module for its firmware version information.
+    pub async fn system_version(&mut self) -> Result<String> {
+        self.request(protocol::system_version()).await
+    }
+
+    /// Queries the module for its firmware version, parsed into its parts, and records
+    /// the model it reports.
+    pub async fn system_firmware_version(&mut self) -> Result<FirmwareVersion> {
+        self.run(operation::DetectModel::new()).await
+    }
+
+    /// Queries the module for its firmware version information, as bytes.
+    pub async fn system_version_bytes(&mut self) -> Result<Vec<u8>> {
+        self.request(protocol::system_version_bytes()).await
+    }
+
+    /// Resets the CPU on the connected module, returning the system version.
+    pub async fn system_module_reset(&mut self) -> Result<Vec<u8>> {
+        self.request(protocol::system_module_reset()).await
+    }
+
+    /// Performs a factory reset on the connected module, returning the system version.
+    pub async fn system_factory_reset(&mut self) -> Result<Vec<u8>> {
+        self.request(protocol::system_factory_reset()).await
+    }
+
+    /// Set the value of the on-MCU nonvolatile memory at the given address.
+    pub async fn system_set_nvm(&mut self, address: NvmAddress, value: u8) -> Result<()> {
+        self.request(protocol::system_set_nvm(address, value)).await
+    }
+
+    /// Get the value of the on-MCU nonvolatile memory at the given address.
+    pub async fn system_get_nvm(&mut self, address: NvmAddress) -> Result<u8> {
+        self.request(protocol::system_get_nvm(address)).await
+    }
+
+    /// Measures the module's supply voltage, in millivolts.
+    pub async fn system_vdd(&mut self) -> Result<u16> {
+        self.request(protocol::system_vdd()).await
+    }
+
+    /// Queries the EUI-64 preprogrammed into the module.
+    pub async fn system_hweui(&mut self) -> Result<Eui64> {
+        self.request(protocol::system_hweui()).await
+    }
+
+    /// Puts the module to sleep for the given duration, in whole milliseconds, resolving
+    /// once it wakes up and reports so.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::AsyncRn2903;
+    /// # use rn2903::sim::Simulator;
+    /// # use std::time::Duration;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut txvr = AsyncRn2903::new(Simulator::new()).await.unwrap();
+    /// txvr.system_sleep(Duration::from_millis(100)).await.unwrap();
+    /// assert!(!txvr.port().asleep());
+    /// # }
+    /// ```
+    pub async fn system_sleep(&mut self, duration: Duration) -> Result<()> {
+        self.run(operation::Sleep::new(duration)?).await
+    }
+
+    /// Puts the module to sleep for the given duration, as
+    /// [`::system_sleep()`](#method.system_sleep) does, without waiting for it to wake
+    /// up. Use [`::system_wake()`](#method.system_wake) to wake it, early or otherwise.
+    pub async fn system_sleep_start(&mut self, duration: Duration) -> Result<()> {
+        let command = protocol::system_sleep(duration)?;
+        self.write_line(command.to_string().as_bytes()).await
+    }
+
+    /// Configures the given pin as a digital output, digital input, or analog input.
+    pub async fn system_set_pin_mode(&mut self, pin: Pin, mode: PinMode) -> Result<()> {
+        self.request(protocol::system_set_pin_mode(pin, mode)?)
+            .await
+    }
+
+    /// Drives the given pin, which must be a digital output, high or low.
+    pub async fn system_set_pin_digital(&mut self, pin: Pin, high: bool) -> Result<()> {
+        self.request(protocol::system_set_pin_digital(pin, high))
+            .await
+    }
+
+    /// Reads the level of the given pin, which must be a digital input or output.
+    pub async fn system_get_pin_digital(&mut self, pin: Pin) -> Result<bool> {
+        self.request(protocol::system_get_pin_digital(pin)).await
+    }
+
+    /// Reads the voltage on the given pin, which must be configured as an analog input,
+    /// as a 10-bit value from 0 to 1023.
+    pub async fn system_get_pin_analog(&mut self, pin: Pin) -> Result<u16> {
+        self.request(protocol::system_get_pin_analog(pin)?).await
+    }
+
+    /// Reads `len` consecutive bytes of nonvolatile memory, starting at the given address.
+    pub async fn read_nvm_range(&mut self, start: NvmAddress, len: usize) -> Result<Vec<u8>> {
+        self.run(operation::ReadNvm::new(start, len)?).await
+    }
+
+    /// Writes the given bytes to consecutive addresses of nonvolatile memory, starting at
+    /// the given address, returning how many bytes were actually written.
+    pub async fn write_nvm_bytes(
+        &mut self,
+        start: NvmAddress,
+        bytes: &[u8],
+        options: NvmWriteOptions,
+    ) -> Result<usize> {
+        self.run(operation::WriteNvm::new(start, bytes, options)?)
+            .await
+    }
+}
+
+/// An asynchronous stream which can send a break condition, as
+/// [`Transport::send_break()`](trait.Transport.html#method.send_break) does for blocking
+/// transports, so that [`AsyncRn2903::system_wake()`](struct.AsyncRn2903.html#method.system_wake)
+/// can wake the module.
+///
+/// Only available with the `async` feature.
+pub trait AsyncBreak {
+    /// Holds the line low for longer than a character, a break condition, which wakes
+    /// the module from sleep and makes it measure the baud rate again from the next
+    /// byte.
+    fn send_break(&mut self) -> io::Result<()>;
+}
+
+/// # Waking
+impl<T: AsyncRead + AsyncWrite + AsyncBreak + Unpin> AsyncRn2903<T> {
+    /// Wakes the module from sleep, early if need be, and waits for it to report so, as
+    /// [`Rn2903::system_wake()`](struct.Rn2903.html#method.system_wake) does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::AsyncRn2903;
+    /// # use rn2903::sim::Simulator;
+    /// # use std::time::Duration;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut txvr = AsyncRn2903::new(Simulator::new()).await.unwrap();
+    /// txvr.system_sleep_start(Duration::from_secs(3600)).await.unwrap();
+    /// assert!(txvr.port().asleep());
+    /// txvr.system_wake().await.unwrap();
+    /// assert!(!txvr.port().asleep());
+    /// # }
+    /// ```
+    pub async fn system_wake(&mut self) -> Result<()> {
+        let mut wake = operation::Wake::new();
+        let mut reply = None;
+        loop {
+            match wake.resume(&mut self.session, reply)? {
+                Flow::Step(Step::Break) => {
+                    self.port.send_break()?;
+                    reply = None;
+                }
+                Flow::Step(step) => reply = self.perform(step).await?,
+                Flow::Done(()) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// # Radio API Functions
+///
+/// These behave as their counterparts on [`Rn2903`](struct.Rn2903.html) do.
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Set the modulation mode used by the radio for transmission and reception.
+    pub async fn radio_set_modulation_mode(&mut self, mode: ModulationMode) -> Result<()> {
+        self.run(operation::SetModulation::new(mode)).await
+    }
+
+    /// Queries the modulation mode used by the radio for transmission and reception.
+    pub async fn radio_get_modulation_mode(&mut self) -> Result<ModulationMode> {
+        self.run(operation::GetModulation::new()).await
+    }
+
+    /// Sets the LoRa spreading factor.
+    pub async fn radio_set_spreading_factor(&mut self, sf: SpreadingFactor) -> Result<()> {
+        self.run(operation::set_spreading_factor(sf)).await
+    }
+
+    /// Queries the LoRa spreading factor.
+    pub async fn radio_get_spreading_factor(&mut self) -> Result<SpreadingFactor> {
+        self.run(operation::get_spreading_factor()).await
+    }
+
+    /// Sets the LoRa signal bandwidth.
+    pub async fn radio_set_bandwidth(&mut self, bw: Bandwidth) -> Result<()> {
+        self.run(operation::set_bandwidth(bw)).await
+    }
+
+    /// Queries the LoRa signal bandwidth.
+    pub async fn radio_get_bandwidth(&mut self) -> Result<Bandwidth> {
+        self.run(operation::get_bandwidth()).await
+    }
+
+    /// Sets the LoRa coding rate.
+    pub async fn radio_set_coding_rate(&mut self, cr: CodingRate) -> Result<()> {
+        self.request(protocol::radio_set_coding_rate(cr)).await
+    }
+
+    /// Queries the LoRa coding rate.
+    pub async fn radio_get_coding_rate(&mut self) -> Result<CodingRate> {
+        self.request(protocol::radio_get_coding_rate()).await
+    }
+
+    /// Sets the carrier frequency in Hz.
+    pub async fn radio_set_frequency(&mut self, hz: u32) -> Result<()> {
+        self.run(operation::set_frequency(self.session.model, hz)?)
+            .await
+    }
+
+    /// Queries the carrier frequency in Hz.
+    pub async fn radio_get_frequency(&mut self) -> Result<u32> {
+        self.run(operation::get_frequency()).await
+    }
+
+    /// Queries the signal-to-noise ratio, in dB, of the last packet received in LoRa
+    /// mode.
+    pub async fn radio_get_snr(&mut self) -> Result<i8> {
+        self.request(protocol::radio_get_snr()).await
+    }
+
+    /// Queries the received signal strength, in dBm, of the last packet received, if
+    /// the firmware supports it.
+    pub async fn radio_get_packet_rssi(&mut self) -> Result<Option<i16>> {
+        self.request(protocol::radio_get_packet_rssi()).await
+    }
+
+    /// Sets the output power in dBm.
+    pub async fn radio_set_power(&mut self, dbm: i8) -> Result<()> {
+        self.request(protocol::radio_set_power(self.session.model, dbm)?)
+            .await
+    }
+
+    /// Queries the output power in dBm.
+    pub async fn radio_get_power(&mut self) -> Result<i8> {
+        self.request(protocol::radio_get_power()).await
+    }
+
+    /// Sets the preamble length in symbols.
+    pub async fn radio_set_preamble_length(&mut self, symbols: u16) -> Result<()> {
+        self.request(protocol::radio_set_preamble_length(symbols))
+            .await
+    }
+
+    /// Queries the preamble length in symbols.
+    pub async fn radio_get_preamble_length(&mut self) -> Result<u16> {
+        self.request(protocol::radio_get_preamble_length()).await
+    }
+
+    /// Enables or disables the CRC header on transmitted packets.
+    pub async fn radio_set_crc(&mut self, enabled: bool) -> Result<()> {
+        self.request(protocol::radio_set_crc(enabled)).await
+    }
+
+    /// Queries whether the CRC header is enabled.
+    pub async fn radio_get_crc(&mut self) -> Result<bool> {
+        self.request(protocol::radio_get_crc()).await
+    }
+
+    /// Enables or disables inversion of the I and Q signals.
+    pub async fn radio_set_iq_inversion(&mut self, enabled: bool) -> Result<()> {
+        self.request(protocol::radio_set_iq_inversion(enabled))
+            .await
+    }
+
+    /// Queries whether inversion of the I and Q signals is enabled.
+    pub async fn radio_get_iq_inversion(&mut self) -> Result<bool> {
+        self.request(protocol::radio_get_iq_inversion()).await
+    }
+
+    /// Sets the LoRa sync word.
+    pub async fn radio_set_sync_word(&mut self, sync: u8) -> Result<()> {
+        self.request(protocol::radio_set_sync_word(sync)).await
+    }
+
+    /// Queries the LoRa sync word.
+    pub async fn radio_get_sync_word(&mut self) -> Result<u8> {
+        self.request(protocol::radio_get_sync_word()).await
+    }
+
+    /// Sets the FSK bit rate in bits per second, between 1 and 300000.
+    pub async fn radio_set_fsk_bitrate(&mut self, bps: u32) -> Result<()> {
+        self.request(protocol::radio_set_fsk_bitrate(bps)?).await
+    }
+
+    /// Queries the FSK bit rate in bits per second.
+    pub async fn radio_get_fsk_bitrate(&mut self) -> Result<u32> {
+        self.request(protocol::radio_get_fsk_bitrate()).await
+    }
+
+    /// Sets the FSK frequency deviation in Hz, between 0 and 200000.
+    pub async fn radio_set_frequency_deviation(&mut self, hz: u32) -> Result<()> {
+        self.request(protocol::radio_set_frequency_deviation(hz)?)
+            .await
+    }
+
+    /// Queries the FSK frequency deviation in Hz.
+    pub async fn radio_get_frequency_deviation(&mut self) -> Result<u32> {
+        self.request(protocol::radio_get_frequency_deviation())
+            .await
+    }
+
+    /// Sets the FSK receiver signal bandwidth.
+    pub async fn radio_set_rx_bandwidth(&mut self, bw: FskBandwidth) -> Result<()> {
+        self.request(protocol::radio_set_rx_bandwidth(bw)).await
+    }
+
+    /// Queries the FSK receiver signal bandwidth.
+    pub async fn radio_get_rx_bandwidth(&mut self) -> Result<FskBandwidth> {
+        self.request(protocol::radio_get_rx_bandwidth()).await
+    }
+
+    /// Sets the FSK automatic frequency correction bandwidth.
+    pub async fn radio_set_afc_bandwidth(&mut self, bw: FskBandwidth) -> Result<()> {
+        self.request(protocol::radio_set_afc_bandwidth(bw)).await
+    }
+
+    /// Queries the FSK automatic frequency correction bandwidth.
+    pub async fn radio_get_afc_bandwidth(&mut self) -> Result<FskBandwidth> {
+        self.request(protocol::radio_get_afc_bandwidth()).await
+    }
+
+    /// Sets the FSK sync word, which must be between 1 and 8 bytes long.
+    pub async fn radio_set_fsk_sync_word(&mut self, sync: &[u8]) -> Result<()> {
+        self.request(protocol::radio_set_fsk_sync_word(sync)?).await
+    }
+
+    /// Queries the FSK sync word.
+    pub async fn radio_get_fsk_sync_word(&mut self) -> Result<Vec<u8>> {
+        self.request(protocol::radio_get_fsk_sync_word()).await
+    }
+
+    /// Sets the radio watchdog timeout in milliseconds. A value of 0 disables the
+    /// watchdog.
+    pub async fn radio_set_watchdog_timeout(&mut self, ms: u32) -> Result<()> {
+        self.run(operation::set_watchdog_timeout(ms)).await
+    }
+
+    /// Queries the radio watchdog timeout in milliseconds.
+    pub async fn radio_get_watchdog_timeout(&mut self) -> Result<u32> {
+        self.run(operation::get_watchdog_timeout()).await
+    }
+
+    /// Open the receiver for the given timeout in symbols (for LoRa) or milliseconds
+    /// (for FSK), returning `Ok(Some(_))` if a valid packet is received or `Ok(None)` if
+    /// no packet is received before the timeout. The wait for the outcome is bounded as
+    /// [`Rn2903::radio_rx()`](struct.Rn2903.html#method.radio_rx) describes.
+    ///
+    /// Dropping the future while it waits for a packet leaves the receiver open; call
+    /// [`::radio_rx_stop()`](#method.radio_rx_stop) to close it.
+    pub async fn radio_rx(&mut self, timeout: u16) -> Result<Option<Vec<u8>>> {
+        self.run(operation::RadioRx::new(timeout)).await
+    }
+
+    /// Closes the receiver if it is open.
+    pub async fn radio_rx_stop(&mut self) -> Result<()> {
+        self.request(protocol::radio_rx_stop()).await
+    }
+
+    /// Transmits the given payload, completing when the module reports that the
+    /// transmission has finished.
+    pub async fn radio_transmit(&mut self, payload: &[u8]) -> Result<Transmission> {
+        self.run(operation::RadioTransmit::new(payload)).await
+    }
+
+    /// Open the receiver for the given timeout, returning the packet along with its
+    /// signal quality and the radio settings it was received with.
+    pub async fn radio_receive(&mut self, timeout: u16) -> Result<Option<ReceivedPacket>> {
+        self.run(operation::RadioReceive::new(timeout)).await
+    }
+}
+
+/// # MAC API Functions
+///
+/// These behave as their counterparts on [`Rn2903`](struct.Rn2903.html) do.
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Pauses the LoRaWAN MAC, returning the number of milliseconds for which it can
+    /// remain paused.
+    pub async fn mac_pause(&mut self) -> Result<u32> {
+        self.request(protocol::mac_pause()).await
+    }
+
+    /// Resumes the LoRaWAN MAC after being paused.
+    pub async fn mac_resume(&mut self) -> Result<()> {
+        self.request(protocol::mac_resume()).await
+    }
+
+    /// Sets the end-device identifier (DevEUI).
+    pub async fn mac_set_device_eui(&mut self, eui: Eui64) -> Result<()> {
+        self.request(protocol::mac_set_device_eui(eui)).await
+    }
+
+    /// Queries the end-device identifier (DevEUI).
+    pub async fn mac_get_device_eui(&mut self) -> Result<Eui64> {
+        self.request(protocol::mac_get_device_eui()).await
+    }
+
+    /// Sets the application identifier (AppEUI).
+    pub async fn mac_set_app_eui(&mut self, eui: Eui64) -> Result<()> {
+        self.request(protocol::mac_set_app_eui(eui)).await
+    }
+
+    /// Queries the application identifier (AppEUI).
+    pub async fn mac_get_app_eui(&mut self) -> Result<Eui64> {
+        self.request(protocol::mac_get_app_eui()).await
+    }
+
+    /// Sets the application key (AppKey).
+    pub async fn mac_set_app_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_app_key(key)).await
+    }
+
+    /// Sets the device address (DevAddr).
+    pub async fn mac_set_device_address(&mut self, address: DevAddr) -> Result<()> {
+        self.request(protocol::mac_set_device_address(address))
+            .await
+    }
+
+    /// Queries the device address (DevAddr).
+    pub async fn mac_get_device_address(&mut self) -> Result<DevAddr> {
+        self.request(protocol::mac_get_device_address()).await
+    }
+
+    /// Sets the network session key (NwkSKey).
+    pub async fn mac_set_network_session_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_network_session_key(key))
+            .await
+    }
+
+    /// Sets the application session key (AppSKey).
+    pub async fn mac_set_app_session_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_app_session_key(key)).await
+    }
+
+    /// Joins a LoRaWAN network with the given activation mode.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{AesKey, AsyncRn2903, Eui64, JoinMode};
+    /// # use rn2903::sim::Simulator;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut txvr = AsyncRn2903::new(Simulator::new()).await.unwrap();
+    /// txvr.mac_set_device_eui(Eui64::new([0x00, 0x04, 0xA3, 0x0B, 0x00, 0x1A, 0x2B, 0x3C]))
+    ///     .await
+    ///     .unwrap();
+    /// txvr.mac_set_app_eui(Eui64::new([0x70, 0xB3, 0xD5, 0x7E, 0xD0, 0x00, 0x00, 0x01]))
+    ///     .await
+    ///     .unwrap();
+    /// txvr.mac_set_app_key(AesKey::new([0x2B; 16])).await.unwrap();
+    /// txvr.mac_join(JoinMode::Otaa).await.unwrap();
+    /// assert!(txvr.mac_tx(false, 1, &[0x01]).await.unwrap().is_none());
+    /// # }
+    /// ```
+    pub async fn mac_join(&mut self, mode: JoinMode) -> Result<()> {
+        self.run(operation::mac_join(mode)).await
+    }
+
+    /// Sends an uplink with the given payload to the given LoRaWAN port, returning any
+    /// downlink the network sent in reply.
+    pub async fn mac_tx(
+        &mut self,
+        confirmed: bool,
+        port: u8,
+        payload: &[u8],
+    ) -> Result<Option<Downlink>> {
+        self.run(operation::mac_tx(confirmed, port, payload)?).await
+    }
+}
+
+/// # MAC Channel Functions
+///
+/// These behave as their counterparts on [`Rn2903`](struct.Rn2903.html) do.
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRn2903<T> {
+    /// Sets the frequency of a channel in Hz. Only channels 3 to 15 of the RN2483 can be
+    /// set.
+    pub async fn mac_set_channel_frequency(&mut self, channel: u8, hz: u32) -> Result<()> {
+        self.request(protocol::mac_set_channel_frequency(
+            self.session.model,
+            channel,
+            hz,
+        )?)
+        .await
+    }
+
+    /// Queries the frequency of a channel in Hz.
+    pub async fn mac_get_channel_frequency(&mut self, channel: u8) -> Result<u32> {
+        self.request(protocol::mac_get_channel_frequency(
+            self.session.model,
+            channel,
+        )?)
+        .await
+    }
+
+    /// Sets the duty cycle of a channel, which is `100 / (value + 1)` percent. Only the
+    /// RN2483 limits the duty cycle of its channels.
+    pub async fn mac_set_channel_duty_cycle(&mut self, channel: u8, value: u16) -> Result<()> {
+        self.request(protocol::mac_set_channel_duty_cycle(
+            self.session.model,
+            channel,
+            value,
+        )?)
+        .await
+    }
+
+    /// Queries the duty cycle of a channel.
+    pub async fn mac_get_channel_duty_cycle(&mut self, channel: u8) -> Result<u16> {
+        self.request(protocol::mac_get_channel_duty_cycle(
+            self.session.model,
+            channel,
+        )?)
+        .await
+    }
+
+    /// Sets the lowest and highest data rate index used on a channel.
+    pub async fn mac_set_channel_data_rate_range(
+        &mut self,
+        channel: u8,
+        min: u8,
+        max: u8,
+    ) -> Result<()> {
+        self.request(protocol::mac_set_channel_data_rate_range(
+            self.session.model,
+            channel,
+            min,
+            max,
+        )?)
+        .await
+    }
+
+    /// Queries the lowest and highest data rate index used on a channel.
+    pub async fn mac_get_channel_data_rate_range(&mut self, channel: u8) -> Result<(u8, u8)> {
+        self.request(protocol::mac_get_channel_data_rate_range(
+            self.session.model,
+            channel,
+        )?)
+        .await
+    }
+
+    /// Enables or disables a channel.
+    pub async fn mac_set_channel_enabled(&mut self, channel: u8, enabled: bool) -> Result<()> {
+        self.request(protocol::mac_set_channel_enabled(
+            self.session.model,
+            channel,
+            enabled,
+        )?)
+        .await
+    }
+
+    /// Queries whether a channel is enabled.
+    pub async fn mac_get_channel_enabled(&mut self, channel: u8) -> Result<bool> {
+        self.request(protocol::mac_get_channel_enabled(
+            self.session.model,
+            channel,
+        )?)
+        .await
+    }
+
+    /// Enables only the channels of the given sub-band of the US915 or AU915 plan, then
+    /// queries every channel to check it took effect.
+    pub async fn mac_enable_only_subband(&mut self, subband: u8) -> Result<()> {
+        let model = self.session.model;
+        self.run(operation::EnableOnlySubband::new(model, subband)?)
+            .await
+    }
+}
+
+/// An in-memory test double, like the simulator, whose `Read` implementation fails with
+/// `TimedOut` when it has nothing to send.
+pub(crate) trait InMemory: io::Read {
+    /// Keeps the waker of a task which found nothing to read, and wakes it once there may
+    /// be: when a line is queued, or when a reply which needs nothing written falls due.
+    fn wake_when_readable(&mut self, waker: Waker);
+}
+
+/// Polls an in-memory reader, which has the task woken once there may be something to
+/// read when there is nothing yet.
+pub(crate) fn poll_read_in_memory<R: InMemory>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<io::Result<()>> {
+    match reader.read(buf.initialize_unfilled()) {
+        Ok(n) => {
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+            reader.wake_when_readable(cx.waker().clone());
+            Poll::Pending
+        }
+        Err(e) => Poll::Ready(Err(e)),
+    }
+}
+
+/// Implements Tokio's `AsyncRead` and `AsyncWrite` for an in-memory test double in terms
+/// of its `Read` and `Write` implementations.
+macro_rules! impl_async_in_memory {
+    ($t:ty) => {
+        impl tokio::io::AsyncRead for $t {
+            fn poll_read(
+                self: core::pin::Pin<&mut Self>,
+                cx: &mut core::task::Context<'_>,
+                buf: &mut tokio::io::ReadBuf<'_>,
+            ) -> core::task::Poll<std::io::Result<()>> {
+                crate::asynchronous::poll_read_in_memory(self.get_mut(), cx, buf)
+            }
+        }
+
+        impl tokio::io::AsyncWrite for $t {
+            fn poll_write(
+                self: core::pin::Pin<&mut Self>,
+                _: &mut core::task::Context<'_>,
+                buf: &[u8],
+            ) -> core::task::Poll<std::io::Result<usize>> {
+                core::task::Poll::Ready(std::io::Write::write(self.get_mut(), buf))
+            }
+
+            fn poll_flush(
+                self: core::pin::Pin<&mut Self>,
+                _: &mut core::task::Context<'_>,
+            ) -> core::task::Poll<std::io::Result<()>> {
+                core::task::Poll::Ready(std::io::Write::flush(self.get_mut()))
+            }
+
+            fn poll_shutdown(
+                self: core::pin::Pin<&mut Self>,
+                _: &mut core::task::Context<'_>,
+            ) -> core::task::Poll<std::io::Result<()>> {
+                core::task::Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
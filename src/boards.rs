@@ -0,0 +1,245 @@
+//! Support for the boards the module is commonly mounted on.
+//!
+//! A [`Board`](struct.Board.html) wraps an [`Rn2903`](../struct.Rn2903.html) along with
+//! a [`PinMap`](struct.PinMap.html) describing how the module's pins are wired on the
+//! board, so the board's LEDs can be driven by name and blink on radio activity. The
+//! [`LoStik`](struct.LoStik.html) is supported out of the box; other boards, like custom
+//! RN2483 breakouts, are described with a `PinMap` of their own.
+//!
+//! # Examples
+//!
+//! ```
+//! # use rn2903::Rn2903;
+//! # use rn2903::sim::Simulator;
+//! use rn2903::boards::{Board, LoStik, PinMap};
+//! use rn2903::Pin;
+//! use std::time::Duration;
+//!
+//! let mut sim = Simulator::new();
+//! sim.queue_packet(b"ping");
+//! let mut stik = LoStik::new(Rn2903::new(sim).unwrap()).unwrap();
+//! stik.set_auto_blink(Some(Duration::from_millis(0)));
+//! stik.txvr().mac_pause().unwrap();
+//!
+//! // The receive LED lights up when a packet arrives...
+//! assert_eq!(stik.radio_rx(100).unwrap(), Some(b"ping".to_vec()));
+//! assert_eq!(stik.txvr().port().pin_output(Pin::Gpio10), Some(true));
+//!
+//! // ...and goes out with the next radio function called through the board.
+//! assert_eq!(stik.radio_rx(100).unwrap(), None);
+//! assert_eq!(stik.txvr().port().pin_output(Pin::Gpio10), Some(false));
+//!
+//! // A breakout with a single LED, wired to GPIO5 and lit by pulling it low.
+//! let pins = PinMap {
+//!     rx_led: Some(Pin::Gpio5),
+//!     tx_led: None,
+//!     leds_active_low: true,
+//! };
+//! let mut breakout = Board::new(Rn2903::new(Simulator::rn2483()).unwrap(), pins).unwrap();
+//! breakout.rx_led(true).unwrap();
+//! assert_eq!(breakout.txvr().port().pin_output(Pin::Gpio5), Some(false));
+//! ```
+
+use crate::{Pin, PinMode, ReceivedPacket, Result, Rn2903, Transmission, Transport};
+use core::ops::{Deref, DerefMut};
+use serialport::prelude::*;
+use std::time::{Duration, Instant};
+
+/// How a board wires the module's pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PinMap {
+    /// The pin driving the LED which indicates reception, if the board has one.
+    pub rx_led: Option<Pin>,
+    /// The pin driving the LED which indicates transmission, if the board has one.
+    pub tx_led: Option<Pin>,
+    /// Whether the LEDs light when their pins are driven low rather than high.
+    pub leds_active_low: bool,
+}
+
+impl PinMap {
+    /// The LoStik USB stick: a blue LED on GPIO10 and a red LED on GPIO11.
+    pub const LOSTIK: PinMap = PinMap {
+        rx_led: Some(Pin::Gpio10),
+        tx_led: Some(Pin::Gpio11),
+        leds_active_low: false,
+    };
+}
+
+/// A module mounted on a board whose pins are wired as the given `PinMap` describes.
+///
+/// The LED pins are configured as digital outputs, and the LEDs switched off, when the
+/// `Board` is created. Once enabled with [`::set_auto_blink()`](#method.set_auto_blink),
+/// the receive LED lights up whenever `::radio_rx()` or `::radio_receive()` returns a
+/// packet, and the transmit LED whenever `::radio_transmit()` finishes a transmission.
+/// Nothing waits for the LED to go out: it is switched off by the first of those radio
+/// functions, or [`::update_leds()`](#method.update_leds), called once the blink duration
+/// has passed. Radio functions called on the `Rn2903` directly do not blink the LEDs.
+pub struct Board<T: Transport = Box<dyn SerialPort>> {
+    txvr: Rn2903<T>,
+    pins: PinMap,
+    auto_blink: Option<Duration>,
+    rx_lit_until: Option<Instant>,
+    tx_lit_until: Option<Instant>,
+}
+
+/// A LoStik: a [`Board`](struct.Board.html) wired as
+/// [`PinMap::LOSTIK`](struct.PinMap.html#associatedconstant.LOSTIK) describes, which
+/// offers every function of `Board` through `Deref`.
+pub struct LoStik<T: Transport = Box<dyn SerialPort>>(Board<T>);
+
+impl<T: Transport> LoStik<T> {
+    /// Wraps the module of a LoStik.
+    pub fn new(txvr: Rn2903<T>) -> Result<Self> {
+        Board::new(txvr, PinMap::LOSTIK).map(LoStik)
+    }
+
+    /// Releases the underlying `Board`.
+    pub fn into_board(self) -> Board<T> {
+        self.0
+    }
+}
+
+impl<T: Transport> Deref for LoStik<T> {
+    type Target = Board<T>;
+
+    fn deref(&self) -> &Board<T> {
+        &self.0
+    }
+}
+
+impl<T: Transport> DerefMut for LoStik<T> {
+    fn deref_mut(&mut self) -> &mut Board<T> {
+        &mut self.0
+    }
+}
+
+impl<T: Transport> Board<T> {
+    /// Wraps the module, wired as the given pin map describes.
+    pub fn new(txvr: Rn2903<T>, pins: PinMap) -> Result<Self> {
+        let mut board = Board {
+            txvr,
+            pins,
+            auto_blink: None,
+            rx_lit_until: None,
+            tx_lit_until: None,
+        };
+        for pin in pins.rx_led.iter().chain(pins.tx_led.iter()) {
+            board
+                .txvr
+                .system_set_pin_mode(*pin, PinMode::DigitalOutput)?;
+        }
+        board.rx_led(false)?;
+        board.tx_led(false)?;
+        Ok(board)
+    }
+
+    /// Returns the pin map of the board.
+    pub fn pins(&self) -> PinMap {
+        self.pins
+    }
+
+    /// Returns the wrapped module, for functions the board does not wrap itself.
+    pub fn txvr(&mut self) -> &mut Rn2903<T> {
+        &mut self.txvr
+    }
+
+    /// Releases the wrapped module, leaving the LEDs as they are.
+    pub fn into_txvr(self) -> Rn2903<T> {
+        self.txvr
+    }
+
+    /// Switches the receive LED on or off, ending any blink. Does nothing if the board has
+    /// none.
+    pub fn rx_led(&mut self, on: bool) -> Result<()> {
+        self.rx_lit_until = None;
+        self.set_led(self.pins.rx_led, on)
+    }
+
+    /// Switches the transmit LED on or off, ending any blink. Does nothing if the board
+    /// has none.
+    pub fn tx_led(&mut self, on: bool) -> Result<()> {
+        self.tx_lit_until = None;
+        self.set_led(self.pins.tx_led, on)
+    }
+
+    /// Sets how long the LEDs stay lit for at least on radio activity, or disables
+    /// blinking with `None`, the default. The LEDs are switched off by the next call
+    /// which finds the duration has passed, so may stay lit for longer; no function
+    /// waits for it.
+    pub fn set_auto_blink(&mut self, duration: Option<Duration>) {
+        self.auto_blink = duration;
+    }
+
+    /// Returns how long the LEDs light up for on radio activity, if at all.
+    pub fn auto_blink(&self) -> Option<Duration> {
+        self.auto_blink
+    }
+
+    /// Switches off the LEDs which have blinked for long enough. The radio functions of the
+    /// board call this before anything else, so it is only needed while they are not
+    /// being called.
+    pub fn update_leds(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if matches!(self.rx_lit_until, Some(until) if now >= until) {
+            self.rx_led(false)?;
+        }
+        if matches!(self.tx_lit_until, Some(until) if now >= until) {
+            self.tx_led(false)?;
+        }
+        Ok(())
+    }
+
+    /// Receives a packet as [`Rn2903::radio_rx()`](../struct.Rn2903.html#method.radio_rx)
+    /// does, blinking the receive LED if one arrives.
+    pub fn radio_rx(&mut self, timeout: u16) -> Result<Option<Vec<u8>>> {
+        self.update_leds()?;
+        let payload = self.txvr.radio_rx(timeout)?;
+        if payload.is_some() {
+            self.rx_lit_until = self.blink(self.pins.rx_led)?;
+        }
+        Ok(payload)
+    }
+
+    /// Receives a packet as
+    /// [`Rn2903::radio_receive()`](../struct.Rn2903.html#method.radio_receive) does,
+    /// blinking the receive LED if one arrives.
+    pub fn radio_receive(&mut self, timeout: u16) -> Result<Option<ReceivedPacket>> {
+        self.update_leds()?;
+        let packet = self.txvr.radio_receive(timeout)?;
+        if packet.is_some() {
+            self.rx_lit_until = self.blink(self.pins.rx_led)?;
+        }
+        Ok(packet)
+    }
+
+    /// Transmits a payload as
+    /// [`Rn2903::radio_transmit()`](../struct.Rn2903.html#method.radio_transmit) does,
+    /// blinking the transmit LED once the transmission finishes.
+    pub fn radio_transmit(&mut self, payload: &[u8]) -> Result<Transmission> {
+        self.update_leds()?;
+        let transmission = self.txvr.radio_transmit(payload)?;
+        self.tx_lit_until = self.blink(self.pins.tx_led)?;
+        Ok(transmission)
+    }
+
+    /// Drives the given LED pin, if there is one.
+    fn set_led(&mut self, pin: Option<Pin>, on: bool) -> Result<()> {
+        match pin {
+            Some(pin) => self
+                .txvr
+                .system_set_pin_digital(pin, on != self.pins.leds_active_low),
+            None => Ok(()),
+        }
+    }
+
+    /// Lights the given LED if blinking is enabled, returning when it may be switched off.
+    fn blink(&mut self, pin: Option<Pin>) -> Result<Option<Instant>> {
+        match self.auto_blink {
+            Some(duration) => {
+                self.set_led(pin, true)?;
+                Ok(Some(Instant::now() + duration))
+            }
+            None => Ok(None),
+        }
+    }
+}
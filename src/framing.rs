@@ -0,0 +1,30 @@
+//! Splitting the byte stream from the module into CRLF-terminated lines.
+
+/// Accumulates bytes read from the module and hands them back one line at a time.
+///
+/// Bytes following a CRLF are kept for the next line, so replies which arrive together in
+/// a single read, or lines split across several reads, are framed correctly.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Appends bytes read from the module.
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns whether nothing has been received since the last line was taken.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Removes and returns the first complete line, without its CRLF, if there is one.
+    pub(crate) fn next_line(&mut self) -> Option<Vec<u8>> {
+        let end = self.buf.windows(2).position(|w| w == b"\x0D\x0A")?;
+        let mut line: Vec<u8> = self.buf.drain(..end + 2).collect();
+        line.truncate(end);
+        Some(line)
+    }
+}
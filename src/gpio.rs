@@ -0,0 +1,174 @@
+//! The module's general-purpose I/O pins.
+
+use crate::protocol;
+use crate::{Result, Rn2903, Transport};
+
+/// A pin of the module which can be controlled with the `sys` pin commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pin {
+    /// GPIO0, analog-capable
+    Gpio0,
+    /// GPIO1, analog-capable
+    Gpio1,
+    /// GPIO2, analog-capable
+    Gpio2,
+    /// GPIO3, analog-capable
+    Gpio3,
+    /// GPIO4, digital only
+    Gpio4,
+    /// GPIO5, analog-capable
+    Gpio5,
+    /// GPIO6, analog-capable
+    Gpio6,
+    /// GPIO7, analog-capable
+    Gpio7,
+    /// GPIO8, analog-capable
+    Gpio8,
+    /// GPIO9, analog-capable
+    Gpio9,
+    /// GPIO10, analog-capable. The blue LED on the LoStik.
+    Gpio10,
+    /// GPIO11, analog-capable. The red LED on the LoStik.
+    Gpio11,
+    /// GPIO12, analog-capable
+    Gpio12,
+    /// GPIO13, analog-capable
+    Gpio13,
+    /// GPIO14, digital only
+    Gpio14,
+    /// UART_CTS, digital only
+    UartCts,
+    /// UART_RTS, digital only
+    UartRts,
+    /// TEST0, digital only
+    Test0,
+    /// TEST1, digital only
+    Test1,
+}
+
+/// Every pin, in order.
+const PINS: &[(Pin, &str)] = &[
+    (Pin::Gpio0, "GPIO0"),
+    (Pin::Gpio1, "GPIO1"),
+    (Pin::Gpio2, "GPIO2"),
+    (Pin::Gpio3, "GPIO3"),
+    (Pin::Gpio4, "GPIO4"),
+    (Pin::Gpio5, "GPIO5"),
+    (Pin::Gpio6, "GPIO6"),
+    (Pin::Gpio7, "GPIO7"),
+    (Pin::Gpio8, "GPIO8"),
+    (Pin::Gpio9, "GPIO9"),
+    (Pin::Gpio10, "GPIO10"),
+    (Pin::Gpio11, "GPIO11"),
+    (Pin::Gpio12, "GPIO12"),
+    (Pin::Gpio13, "GPIO13"),
+    (Pin::Gpio14, "GPIO14"),
+    (Pin::UartCts, "UART_CTS"),
+    (Pin::UartRts, "UART_RTS"),
+    (Pin::Test0, "TEST0"),
+    (Pin::Test1, "TEST1"),
+];
+
+impl Pin {
+    /// Returns every pin, from GPIO0 to TEST1.
+    pub fn all() -> impl Iterator<Item = Pin> {
+        PINS.iter().map(|(pin, _)| *pin)
+    }
+
+    /// Returns whether the pin can be read as an analog input: GPIO0 to GPIO3 and GPIO5
+    /// to GPIO13.
+    pub fn supports_analog(self) -> bool {
+        !matches!(
+            self,
+            Pin::Gpio4 | Pin::Gpio14 | Pin::UartCts | Pin::UartRts | Pin::Test0 | Pin::Test1
+        )
+    }
+
+    pub(crate) fn token(self) -> &'static str {
+        PINS.iter()
+            .find(|(pin, _)| *pin == self)
+            .map(|(_, token)| *token)
+            .expect("every Pin is listed in PINS")
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        PINS.iter().find(|(_, t)| *t == token).map(|(pin, _)| *pin)
+    }
+}
+
+/// The functions a pin can be configured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinMode {
+    /// Digital output, the default for every pin.
+    DigitalOutput,
+    /// Digital input.
+    DigitalInput,
+    /// Analog input, only available on pins for which `Pin::supports_analog()` is true.
+    Analog,
+}
+
+impl PinMode {
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            PinMode::DigitalOutput => "digout",
+            PinMode::DigitalInput => "digin",
+            PinMode::Analog => "ana",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "digout" => Some(PinMode::DigitalOutput),
+            "digin" => Some(PinMode::DigitalInput),
+            "ana" => Some(PinMode::Analog),
+            _ => None,
+        }
+    }
+}
+
+/// # GPIO Functions
+///
+/// # Example
+///
+/// ```
+/// # use rn2903::{Pin, PinMode, Rn2903};
+/// # use rn2903::sim::Simulator;
+/// let mut sim = Simulator::new();
+/// sim.set_pin_input(Pin::Gpio3, 512);
+/// let mut txvr = Rn2903::new(sim).unwrap();
+///
+/// txvr.system_set_pin_digital(Pin::Gpio10, true).unwrap();
+/// assert_eq!(txvr.port().pin_output(Pin::Gpio10), Some(true));
+///
+/// txvr.system_set_pin_mode(Pin::Gpio3, PinMode::Analog).unwrap();
+/// assert_eq!(txvr.system_get_pin_analog(Pin::Gpio3).unwrap(), 512);
+/// assert!(txvr.system_get_pin_analog(Pin::Gpio4).is_err());
+/// ```
+impl<T: Transport> Rn2903<T> {
+    /// Configures the given pin as a digital output, digital input, or analog input.
+    ///
+    /// Configuring a pin which does not support analog input as one is rejected with
+    /// `Error::OutOfRange` without being sent.
+    pub fn system_set_pin_mode(&mut self, pin: Pin, mode: PinMode) -> Result<()> {
+        self.request(protocol::system_set_pin_mode(pin, mode)?)
+    }
+
+    /// Drives the given pin, which must be a digital output, high or low.
+    pub fn system_set_pin_digital(&mut self, pin: Pin, high: bool) -> Result<()> {
+        self.request(protocol::system_set_pin_digital(pin, high))
+    }
+
+    /// Reads the level of the given pin, which must be a digital input or output.
+    pub fn system_get_pin_digital(&mut self, pin: Pin) -> Result<bool> {
+        self.request(protocol::system_get_pin_digital(pin))
+    }
+
+    /// Reads the voltage on the given pin, which must be configured as an analog input,
+    /// as a 10-bit value from 0 to 1023.
+    ///
+    /// Pins which do not support analog input are rejected with `Error::OutOfRange`
+    /// without being sent.
+    pub fn system_get_pin_analog(&mut self, pin: Pin) -> Result<u16> {
+        self.request(protocol::system_get_pin_analog(pin)?)
+    }
+}
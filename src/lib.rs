@@ -5,9 +5,13 @@
 //!
 //! This crate provides a safe, idiomatic interface using cross-platform native serial
 //! functionality via `serialport`. This supports, for instance, a LoStik connected to a USB
-//! TTY or virtual COM port, or a RN2903 connected via a TTL serial interface.
+//! TTY or virtual COM port, or a RN2903 connected via a TTL serial interface. Other links,
+//! like a TCP serial bridge or an in-memory pipe, can be used by implementing the
+//! [`Transport` trait](trait.Transport.html).
 //!
 //! See the [`Rn2903` struct](struct.Rn2903.html) for the bulk of the crate's functionality.
+//! With the `async` feature enabled, `AsyncRn2903` offers the same functionality to Tokio
+//! applications over any `AsyncRead + AsyncWrite` stream, such as one from `tokio-serial`.
 //!
 //! # Examples
 //!
@@ -32,9 +36,43 @@
 // a `Result<T, rn2903::Error>`.
 #[macro_use]
 extern crate quick_error;
-use hex;
 use std::io;
 
+#[cfg(feature = "async")]
+#[macro_use]
+mod asynchronous;
+pub mod boards;
+mod framing;
+mod gpio;
+mod mac;
+pub mod mock;
+mod nvm;
+#[macro_use]
+mod operation;
+pub mod protocol;
+mod radio;
+mod receiver;
+pub mod region;
+pub mod sim;
+mod store;
+mod transport;
+mod version;
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncBreak, AsyncRn2903};
+use framing::LineBuffer;
+pub use gpio::{Pin, PinMode};
+pub use mac::{AesKey, DevAddr, Downlink, Eui64, JoinMode};
+pub use nvm::{NvmWriteOptions, NVM_SIZE};
+use operation::{Flow, Operation, Session, Step};
+pub use radio::{
+    Bandwidth, CodingRate, FskBandwidth, GaussianBt, ModulationMode, ReceivedPacket,
+    SpreadingFactor, Transmission,
+};
+pub use receiver::Receiver;
+pub use store::{NvmRecord, NvmStore};
+pub use transport::Transport;
+pub use version::{FirmwareVersion, Model};
+
 quick_error! {
     /// The primary error type used for fallible operations on the RN2903.
     #[derive(Debug)]
@@ -44,7 +82,7 @@ quick_error! {
         /// access the specified port.
         ConnectionFailed(err: serialport::Error) {
             cause(err)
-            description(err.description())
+            display("{}", err)
             from()
         }
         /// The device to which the serial link is connected does not appear to be
@@ -78,7 +116,7 @@ quick_error! {
         /// host operating system closed the serial port for some reason.
         Disconnected(err: io::Error) {
             cause(err)
-            description(err.description())
+            display("{}", err)
             from()
         }
 
@@ -86,11 +124,104 @@ quick_error! {
             description("the radio transmission was unsuccessful")
             display("if transmission was unsuccessful (interrupted by radio Watchdog Timer time-out)")
         }
+        /// The radio reported an error while receiving before its watchdog could have
+        /// closed the receiver, for instance because a damaged packet arrived.
+        ReceptionUnsuccessful {
+            description("the radio reception was unsuccessful")
+            display("The radio reported an error while receiving, before the watchdog timer could have expired.")
+        }
 
         InvalidParam {
             description("the parameter is not valid")
             display("the parameter is not valid")
         }
+        /// A value passed to a function lies outside the range the module accepts, so
+        /// the command was not sent.
+        OutOfRange { parameter: &'static str, value: String } {
+            description("a parameter is out of range")
+            display("The value {} is out of range for the {}.", value, parameter)
+        }
+        /// The module did not answer the given command before the deadline for doing so
+        /// passed. The module may be hung and in need of a reset.
+        Timeout { command: String } {
+            description("the module did not answer in time")
+            display("The module did not answer '{}' in time.", command)
+        }
+        /// The keys and identifiers required for the requested LoRaWAN join mode have not
+        /// all been set.
+        KeysNotInitialized {
+            description("the LoRaWAN keys are not initialized")
+            display("The keys and identifiers required to join a LoRaWAN network have not all been set.")
+        }
+        /// All LoRaWAN channels are currently unavailable, usually because of duty cycle
+        /// restrictions.
+        NoFreeChannel {
+            description("no LoRaWAN channel is free")
+            display("No LoRaWAN channel is currently free to transmit on.")
+        }
+        /// The module is in the silent immediately state, and may not transmit until it
+        /// is reset or the MAC is told otherwise.
+        Silent {
+            description("the module is silenced")
+            display("The module has been silenced by the network and may not transmit.")
+        }
+        /// The LoRaWAN MAC is paused, and must be resumed before the requested operation
+        /// can be performed.
+        MacPaused {
+            description("the LoRaWAN MAC is paused")
+            display("The LoRaWAN MAC is paused, but the requested operation requires it.")
+        }
+        /// The network rejected the join attempt, or did not answer it.
+        JoinDenied {
+            description("the LoRaWAN join was denied")
+            display("The attempt to join the LoRaWAN network was denied.")
+        }
+        /// The module has not joined a LoRaWAN network, so cannot send uplinks.
+        NotJoined {
+            description("the module has not joined a LoRaWAN network")
+            display("The module must join a LoRaWAN network before sending uplinks.")
+        }
+        /// The LoRaWAN frame counter has rolled over, and the module must rejoin the
+        /// network before sending more uplinks.
+        FrameCounterRejoinNeeded {
+            description("the LoRaWAN frame counter has rolled over")
+            display("The LoRaWAN frame counter has rolled over; the module must rejoin the network.")
+        }
+        /// The payload is too long for the current LoRaWAN data rate.
+        InvalidDataLength {
+            description("the payload is too long")
+            display("The payload is too long to be sent at the current LoRaWAN data rate.")
+        }
+        /// The LoRaWAN uplink was not sent successfully, or a confirmed uplink was not
+        /// acknowledged by the network.
+        MacTransmissionFailed {
+            description("the LoRaWAN transmission failed")
+            display("The LoRaWAN uplink failed or was not acknowledged by the network.")
+        }
+        /// A byte of nonvolatile memory did not hold the value just written to it when
+        /// read back.
+        NvmVerifyFailed { address: u16, expected: u8, found: u8 } {
+            description("a nonvolatile memory write did not verify")
+            display("Wrote {:#04x} to NVM address {:#x}, but read back {:#04x}.",
+                expected, address, found)
+        }
+        /// A channel did not report the status it was just set to when queried.
+        ChannelVerifyFailed { channel: u8, enabled: bool } {
+            description("a channel status did not verify")
+            display("Set channel {} {}, but it did not read back so.",
+                channel, if *enabled { "on" } else { "off" })
+        }
+        /// The user area of nonvolatile memory does not hold an `NvmStore`: it has never
+        /// been saved to this module, or the memory has been used some other way.
+        NvmStoreUninitialized {
+            description("no NVM store is present")
+            display("The module's nonvolatile memory does not hold a record store.")
+        }
+        /// The `NvmStore` in nonvolatile memory is damaged, for the given reason.
+        NvmStoreCorrupt(reason: &'static str) {
+            description("the NVM store is corrupt")
+            display("The record store in the module's nonvolatile memory is corrupt: {}.", reason)
+        }
     }
 }
 
@@ -98,6 +229,13 @@ impl Error {
     fn bad_response<S: Into<String>, T: Into<String>>(expected: S, response: T) -> Self {
         Self::BadResponse(expected.into(), response.into())
     }
+
+    fn out_of_range<V: ToString>(parameter: &'static str, value: V) -> Self {
+        Self::OutOfRange {
+            parameter,
+            value: value.to_string(),
+        }
+    }
 }
 
 /// Universal `Result` wrapper for the RN2903 interface.
@@ -107,19 +245,30 @@ pub type Result<T> = std::result::Result<T, Error>;
 // exports all the configuration information needed to configure a serial port to
 // communicate correctly with an RN2903.
 
-use core::convert::AsRef;
+use core::convert::{AsRef, TryFrom};
 use core::time::Duration;
 use serialport::prelude::*;
 use std::ffi::OsStr;
-use std::io::prelude::*;
 use std::thread;
+use std::time::Instant;
+
+/// The default amount of time to wait for the module to answer a command.
+///
+/// The module normally answers within a few milliseconds; this leaves plenty of room for
+/// slower commands like `sys reset`. It can be changed with
+/// [`Rn2903::set_response_timeout()`](struct.Rn2903.html#method.set_response_timeout).
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a single read may block while waiting on a reply with no deadline, such as
+/// the second reply to `radio rx 0`.
+const UNBOUNDED_READ_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Returns the `SerialPortSettings` corresponding to the default settings of
 /// an RNB2903.
 ///
 /// Information obtained from Microchip document 40001811 revision B. Timeout is by
-/// default set to a very long time; this is sometimes modified on the `SerialPort` itself
-/// during certain operations.
+/// default set to [`DEFAULT_RESPONSE_TIMEOUT`](constant.DEFAULT_RESPONSE_TIMEOUT.html); the
+/// `Rn2903` adjusts it on the `SerialPort` itself as it waits on each reply.
 ///
 /// # Examples
 ///
@@ -142,7 +291,7 @@ pub fn serial_config() -> SerialPortSettings {
         flow_control: FlowControl::None,
         parity: Parity::None,
         stop_bits: StopBits::One,
-        timeout: Duration::new(65535, 0),
+        timeout: DEFAULT_RESPONSE_TIMEOUT,
     }
 }
 
@@ -179,8 +328,13 @@ pub fn bytes_to_string(bytes: &[u8]) -> String {
 ///     thread::sleep(Duration::from_millis(1000));
 /// }
 /// ```
-pub struct Rn2903 {
-    port: Box<dyn SerialPort>,
+pub struct Rn2903<T = Box<dyn SerialPort>> {
+    port: T,
+    input: LineBuffer,
+    last_command: Vec<u8>,
+    session: Session,
+    response_timeout: Duration,
+    write_delay: Option<Duration>,
 }
 
 /// # Meta (type) Functions
@@ -190,7 +344,8 @@ pub struct Rn2903 {
 /// ## Creating an `Rn2903`
 /// There are several  ways to create a `Rn2903` wrapper for an RN2903 serial connection.
 /// `::new_at()` is the recommended method, but `::new()` can be useful if the platform
-/// does not support named serial ports, or some extra configuration is needed.
+/// does not support named serial ports, some extra configuration is needed, or the module
+/// is reached over something other than a local serial port.
 impl Rn2903 {
     /// Opens a new connection to a module at the given path or port name, with the
     /// default (and usually correct) settings from
@@ -208,31 +363,52 @@ impl Rn2903 {
         let sp = serialport::open_with_settings(&port_name, &serial_config())?;
         Self::new(sp)
     }
+}
 
-    /// Open a new connection to a module over the connection described by the given
+impl<T: Transport> Rn2903<T> {
+    /// Open a new connection to a module over the given `Transport`, such as a
     /// `SerialPort` trait object.
-    pub fn new(port: Box<dyn SerialPort>) -> Result<Self> {
+    pub fn new(port: T) -> Result<Self> {
         let mut new = Self::new_unchecked(port);
-        let version = new.system_version()?;
-        // RN2483 and RN2903 are the same, just EU and US chip, but talk the same
-        if &version[0..6] != "RN2903" && &version[0..6] != "RN2483" {
-            Err(Error::WrongDevice(version))
-        } else {
-            Ok(new)
-        }
+        new.system_firmware_version()?;
+        Ok(new)
     }
 
-    /// Open a new connection to a module over the connection described by the given
-    /// `SerialPort` trait object without performing a `sys get ver` check.
+    /// Open a new connection to a module over the given `Transport` without performing
+    /// a `sys get ver` check.
     ///
     /// The results of operations on a `Rn2903` struct that does _not_ represent an
     /// actual connection to an RN2903 module are completely unpredictable, and may
     /// result in lots of badness (though not memory unsafety).
-    pub fn new_unchecked(port: Box<dyn SerialPort>) -> Self {
-        Self { port }
+    pub fn new_unchecked(port: T) -> Self {
+        Self {
+            port,
+            input: LineBuffer::default(),
+            last_command: Vec::new(),
+            session: Session::default(),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            write_delay: None,
+        }
+    }
+
+    /// Returns the model of the connected module, detected from its firmware version by
+    /// `::new()` or [`::system_firmware_version()`](#method.system_firmware_version), or
+    /// `None` if it has not been.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Model, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let txvr = Rn2903::new(Simulator::rn2483()).unwrap();
+    /// assert_eq!(txvr.model(), Some(Model::Rn2483));
+    /// ```
+    pub fn model(&self) -> Option<Model> {
+        self.session.model
     }
 
-    /// Acquires temporary direct access to the captured `SerialPort` trait object.
+    /// Acquires temporary direct access to the captured `Transport`, for instance a
+    /// `SerialPort` trait object.
     ///
     /// Use this access to, for example, reconfigure the connection on the fly,
     /// or set flags that will be used by devices this crate is unaware of.
@@ -253,93 +429,291 @@ impl Rn2903 {
     /// txvr.port().write_request_to_send(false)
     ///     .expect("Could not set RTS. Error");
     /// ```
-    pub fn port(&mut self) -> &mut dyn SerialPort {
-        &mut *self.port
+    pub fn port(&mut self) -> &mut T {
+        &mut self.port
+    }
+
+    /// Consumes the `Rn2903`, returning the captured `Transport`.
+    pub fn into_port(self) -> T {
+        self.port
+    }
+
+    /// Returns the amount of time to wait for the module to answer a command.
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    /// Sets the amount of time to wait for the module to answer a command. By default,
+    /// this is [`DEFAULT_RESPONSE_TIMEOUT`](constant.DEFAULT_RESPONSE_TIMEOUT.html).
+    ///
+    /// Commands which are not answered in time fail with `Error::Timeout`. Operations
+    /// which are expected to take longer, like receiving a packet, extend this as needed;
+    /// use [`::transact_with_timeout()`](#method.transact_with_timeout) to do the same
+    /// for raw commands.
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    /// Returns the delay inserted after writing each command, if any.
+    pub fn write_delay(&self) -> Option<Duration> {
+        self.write_delay
+    }
+
+    /// Sets a delay to be inserted after writing each command, before waiting for the
+    /// reply. By default, there is no delay.
+    ///
+    /// Some LoStik units have the hiccups and drop the start of a reply if it is read too
+    /// eagerly. Setting a delay of 500 milliseconds reproduces the timing of earlier
+    /// versions of this crate, which is known to work around this.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rn2903::Rn2903;
+    /// # use std::time::Duration;
+    /// let mut txvr = Rn2903::new_at("/dev/ttyUSB0")
+    ///     .expect("Could not open device. Error");
+    /// txvr.set_write_delay(Some(Duration::from_millis(500)));
+    /// ```
+    pub fn set_write_delay(&mut self, delay: Option<Duration>) {
+        self.write_delay = delay;
     }
 }
 
 /// # Low-level Communications
-impl Rn2903 {
+impl<T: Transport> Rn2903<T> {
     /// Writes the specified command to the module and returns a single line in response.
     ///
     /// This function adds the CRLF to the given command and returns the response without
     /// the CRLF.
     ///
     /// This is the preferred low-level communication method, since the RN2903 is supposed
-    /// to respond with a single line to every command.
+    /// to respond with a single line to every command. As with
+    /// [`::send_line()`](#method.send_line), radio settings the crate remembers are
+    /// forgotten.
     pub fn transact(&mut self, command: &[u8]) -> Result<Vec<u8>> {
         self.send_line(command)?;
         self.read_line()
     }
 
-    /// Convenience function for situations where only one response is expected according
-    /// to the module's documentation. Receiving another response means something wacky
-    /// is going on.
-    fn transact_expecting(&mut self, command: &[u8], expectation: &[u8]) -> Result<()> {
-        let bytes = self.transact(command)?;
-        if bytes != expectation {
-            Err(Error::bad_response(
-                bytes_to_string(expectation),
-                bytes_to_string(&bytes),
-            ))
-        } else {
-            Ok(())
+    /// Writes the specified command to the module and returns a single line in response,
+    /// as `::transact()` does, but waits up to the given amount of time for the response
+    /// instead of the [response timeout](#method.set_response_timeout).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Error, Rn2903};
+    /// # use rn2903::mock::MockTransport;
+    /// # use std::time::Duration;
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"sys get ver", &[]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    ///
+    /// match txvr.transact_with_timeout(b"sys get ver", Duration::from_millis(10)) {
+    ///     Err(Error::Timeout { command }) => assert_eq!(command, "sys get ver"),
+    ///     other => panic!("unexpected result {:?}", other),
+    /// }
+    /// ```
+    pub fn transact_with_timeout(&mut self, command: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+        self.send_line(command)?;
+        self.read_line_until(Some(Instant::now() + timeout))
+    }
+
+    /// Sends the command of the given request and interprets the reply.
+    fn request<V>(&mut self, request: protocol::Request<V>) -> Result<V> {
+        self.run(operation::Single::new(request))
+    }
+
+    /// Performs the steps of the given operation until it is done.
+    fn run<O: Operation>(&mut self, mut operation: O) -> Result<O::Output> {
+        let mut reply = None;
+        loop {
+            match operation.resume(&mut self.session, reply)? {
+                Flow::Step(step) => reply = self.perform(step)?,
+                Flow::Done(output) => return Ok(output),
+            }
+        }
+    }
+
+    /// Performs a single step of an operation, returning the line it read, if any.
+    fn perform(&mut self, step: Step) -> Result<Option<Vec<u8>>> {
+        match step {
+            Step::Transact(line) => {
+                self.write_line(line.as_bytes())?;
+                self.read_line().map(Some)
+            }
+            Step::Write(line) => self.write_line(line.as_bytes()).map(|()| None),
+            Step::ReadLine(wait) => {
+                let deadline = wait.map(|wait| Instant::now() + self.response_timeout + wait);
+                self.read_line_until(deadline).map(Some)
+            }
+            Step::Poll(wait) => self.poll_line(wait),
+            Step::WriteBytes(bytes) => {
+                self.port.write_all(&bytes)?;
+                self.port.flush()?;
+                Ok(None)
+            }
+            Step::Break => self.port.send_break().map(|()| None).map_err(Error::from),
+            Step::Drain(quiet) => self.drain(quiet).map(|()| None),
+        }
+    }
+
+    /// Discards everything received so far, and anything more until nothing arrives for
+    /// the given time, or the response timeout passes.
+    fn drain(&mut self, quiet: Duration) -> Result<()> {
+        let deadline = Instant::now() + self.response_timeout;
+        loop {
+            self.input = LineBuffer::default();
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            self.fill_input(quiet.min(deadline - now))?;
+            if self.input.is_empty() {
+                return Ok(());
+            }
         }
     }
 
     /// Writes the specified command to the module, adding a CRLF and flushing the buffer.
     ///
     /// Using [`::transact()`](#method.transact) is preferred.
+    ///
+    /// The command may change any radio setting, so the settings the crate remembers to
+    /// avoid querying them again are forgotten, and queried again when needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Error, Rn2903};
+    /// # use rn2903::mock::MockTransport;
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"radio get mod", &[b"lora"]);
+    /// mock.expect(b"radio set mod fsk", &[b"ok"]);
+    /// mock.expect(b"radio get mod", &[b"fsk"]);
+    /// mock.expect(b"radio get bt", &[b"none"]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    ///
+    /// txvr.radio_get_modulation_mode().unwrap();
+    /// txvr.transact(b"radio set mod fsk").unwrap();
+    /// // Too long for FSK, which is found out by querying the mode again.
+    /// assert!(matches!(
+    ///     txvr.radio_transmit(&[0; 100]),
+    ///     Err(Error::OutOfRange { .. })
+    /// ));
+    /// txvr.port().assert_finished();
+    /// ```
     pub fn send_line(&mut self, line: &[u8]) -> Result<()> {
+        self.session.radio = Default::default();
+        self.write_line(line)
+    }
+
+    /// Writes a command line built by the crate, whose effect on the radio settings is
+    /// accounted for by the operation sending it.
+    fn write_line(&mut self, line: &[u8]) -> Result<()> {
         let bytes: Vec<u8> = line.iter().chain(b"\x0D\x0A".iter()).cloned().collect();
-        let mut cursor = 0;
-        while cursor < bytes.len() {
-            cursor += self.port.write(&bytes[cursor..])?;
-        }
+        self.last_command = line.to_vec();
+        self.port.write_all(&bytes)?;
         self.port.flush()?;
-        thread::sleep(Duration::from_millis(500));
+        if let Some(delay) = self.write_delay {
+            thread::sleep(delay);
+        }
         Ok(())
     }
 
     /// Reads bytes from the device until a CRLF is encountered, then returns the bytes
     /// read, not including the CRLF.
     ///
+    /// Waits at most the [response timeout](#method.set_response_timeout) for the line
+    /// to arrive, failing with `Error::Timeout` otherwise. Any bytes received after the
+    /// CRLF are kept for the next line.
+    ///
     /// Using [`::transact()`](#method.transact) is preferred.
-    // This operation waits 12ms between each 32-byte read because the LoStick has
-    // the hiccups.
     pub fn read_line(&mut self) -> Result<Vec<u8>> {
-        let mut vec = Vec::with_capacity(32);
+        let deadline = Instant::now() + self.response_timeout;
+        self.read_line_until(Some(deadline))
+    }
+
+    /// Waits up to the given amount of time for a line from the module, returning
+    /// `Ok(None)` if no complete line arrives in time.
+    ///
+    /// This is useful for collecting lines the module sends without being prompted by a
+    /// command, like the `radio_rx` notification that follows `radio rx`. Partial lines
+    /// are kept until the rest arrives.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::mock::MockTransport;
+    /// # use std::time::Duration;
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"radio rx 0", &[b"ok"]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    ///
+    /// assert_eq!(txvr.transact(b"radio rx 0").unwrap(), b"ok");
+    /// assert_eq!(txvr.poll_line(Duration::from_millis(10)).unwrap(), None);
+    /// txvr.port().push_line(b"radio_rx  CAFE");
+    /// let line = txvr.poll_line(Duration::from_millis(10)).unwrap();
+    /// assert_eq!(line, Some(b"radio_rx  CAFE".to_vec()));
+    /// assert_eq!(txvr.poll_line(Duration::from_millis(10)).unwrap(), None);
+    /// ```
+    pub fn poll_line(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>> {
+        let deadline = Instant::now() + timeout;
         loop {
-            let mut buf = [0; 32];
-            self.port.read(&mut buf)?;
-            vec.extend_from_slice(&buf);
-
-            // Check if crlf was added to the buffer.
-            let mut found_lf = false;
-            let mut found_crlf = false;
-            for byte in vec.iter().rev() {
-                if found_lf {
-                    if *byte == b'\x0D' {
-                        found_crlf = true;
-                        break;
-                    }
-                } else {
-                    found_lf = *byte == b'\x0A';
-                }
+            if let Some(line) = self.input.next_line() {
+                return Ok(Some(line));
             }
-            if found_crlf {
-                break;
-            } else {
-                thread::sleep(Duration::from_millis(12));
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
             }
+            self.fill_input(deadline - now)?;
         }
+    }
 
-        // Remove zeroes and crlf
-        while (b"\x00\x0D\x0A").contains(&vec[vec.len() - 1]) {
-            vec.pop();
+    /// Reads a line as `::read_line()` does, giving up at the given deadline, or waiting
+    /// forever if there is none.
+    fn read_line_until(&mut self, deadline: Option<Instant>) -> Result<Vec<u8>> {
+        loop {
+            if let Some(line) = self.input.next_line() {
+                return Ok(line);
+            }
+            let wait = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Error::Timeout {
+                            command: bytes_to_string(&self.last_command),
+                        });
+                    }
+                    deadline - now
+                }
+                None => UNBOUNDED_READ_TIMEOUT,
+            };
+            self.fill_input(wait)?;
         }
+    }
 
-        Ok(vec)
+    /// Performs a single read from the transport into the line buffer, blocking for at
+    /// most the given amount of time. Running out of time is not an error.
+    fn fill_input(&mut self, wait: Duration) -> Result<()> {
+        self.port.set_timeout(wait)?;
+        let mut buf = [0; 64];
+        match self.port.read(&mut buf) {
+            Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => {
+                self.input.extend(&buf[..n]);
+                Ok(())
+            }
+            Err(e) => match e.kind() {
+                io::ErrorKind::TimedOut
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::Interrupted => Ok(()),
+                _ => Err(e.into()),
+            },
+        }
     }
 }
 
@@ -352,6 +726,9 @@ impl NvmAddress {
     /// Create a new `NvmAddress` from a `u16`. The given value must be between 0x300 and
     /// 0x3FF.
     ///
+    /// Use `NvmAddress::try_from()` for addresses which may be invalid, like those read
+    /// from configuration.
+    ///
     /// # Panics
     /// Panics if the given value is not between 0x300 and 0x3FF.
     pub fn new(value: u16) -> NvmAddress {
@@ -371,21 +748,65 @@ impl NvmAddress {
     }
 }
 
+/// Checks the address is between 0x300 and 0x3FF, failing with `Error::OutOfRange`
+/// otherwise.
+///
+/// # Example
+///
+/// ```
+/// # use rn2903::NvmAddress;
+/// use std::convert::TryFrom;
+///
+/// assert_eq!(NvmAddress::try_from(0x3A0).unwrap().inner(), 0x3A0);
+/// assert!(NvmAddress::try_from(0x400).is_err());
+/// ```
+impl TryFrom<u16> for NvmAddress {
+    type Error = Error;
+
+    fn try_from(value: u16) -> Result<NvmAddress> {
+        if (0x300..=0x3FF).contains(&value) {
+            Ok(NvmAddress(value))
+        } else {
+            Err(Error::out_of_range("NVM address", format!("{:#x}", value)))
+        }
+    }
+}
+
 /// # System API Functions
-impl Rn2903 {
+impl<T: Transport> Rn2903<T> {
     /// Queries the module for its firmware version information.
     ///
     /// Returns a `String` like `RN2903 1.0.3 Aug  8 2017 15:11:09`
     pub fn system_version(&mut self) -> Result<String> {
-        let bytes = self.transact(b"sys get ver")?;
-        Ok(bytes_to_string(&bytes))
+        self.request(protocol::system_version())
+    }
+
+    /// Queries the module for its firmware version, parsed into its parts, and records
+    /// the [model](#method.model) it reports.
+    ///
+    /// Fails with `Error::WrongDevice` if the reply does not name an RN2903 or RN2483
+    /// firmware version.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Model, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new_unchecked(Simulator::new());
+    /// let version = txvr.system_firmware_version().unwrap();
+    /// assert_eq!(version.model, Model::Rn2903);
+    /// assert_eq!((version.major, version.minor, version.patch), (1, 0, 5));
+    /// assert_eq!(txvr.model(), Some(Model::Rn2903));
+    /// ```
+    pub fn system_firmware_version(&mut self) -> Result<FirmwareVersion> {
+        self.run(operation::DetectModel::new())
     }
 
     /// Queries the module for its firmware version information.
     ///
     /// As `::system_version()`, but returns bytes.
     pub fn system_version_bytes(&mut self) -> Result<Vec<u8>> {
-        self.transact(b"sys get ver")
+        self.request(protocol::system_version_bytes())
     }
 
     /// Resets the CPU on the connected module. State in memory is lost and the MAC
@@ -393,7 +814,7 @@ impl Rn2903 {
     ///
     /// Returns the system version, like `::system_version_bytes()`.
     pub fn system_module_reset(&mut self) -> Result<Vec<u8>> {
-        self.transact(b"sys reset")
+        self.request(protocol::system_module_reset())
     }
 
     /// Performs a factory reset on the connected module. All EEPROM values are
@@ -401,125 +822,104 @@ impl Rn2903 {
     ///
     /// Returns the system version, like `::system_version_bytes()`.
     pub fn system_factory_reset(&mut self) -> Result<Vec<u8>> {
-        self.transact(b"sys factoryRESET")
+        self.request(protocol::system_factory_reset())
     }
 
     /// Set the value of the on-MCU nonvolatile memory at the given address to the given
     /// value.
     pub fn system_set_nvm(&mut self, address: NvmAddress, value: u8) -> Result<()> {
-        self.transact_expecting(
-            &format!("sys set nvm {:x} {:x}", address.inner(), value).into_bytes(),
-            b"ok",
-        )
+        self.request(protocol::system_set_nvm(address, value))
     }
 
     /// Get the value of the on-MCU nonvolatile memory at the given address.
     pub fn system_get_nvm(&mut self, address: NvmAddress) -> Result<u8> {
-        let response = bytes_to_string(
-            &self.transact(&format!("sys get nvm {:x}", address.inner()).into_bytes())?,
-        );
-        match u8::from_str_radix(&response, 16) {
-            Ok(v) => Ok(v),
-            Err(_) => Err(Error::bad_response("<integer>", response)),
-        }
+        self.request(protocol::system_get_nvm(address))
     }
-}
 
-/// Types of modulation available for transmitting and receiving packets.
-#[derive(Debug, PartialEq, Eq)]
-pub enum ModulationMode {
-    /// Regular digital frequency shift keying mode
-    Fsk,
-    /// LoRa chirp spread spectrum mode
-    LoRa, // TODO: GFSK with radio set bt <value>
-}
+    /// Measures the module's supply voltage, in millivolts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// let mut sim = Simulator::new();
+    /// sim.set_vdd(3_150);
+    /// let mut txvr = Rn2903::new(sim).unwrap();
+    /// assert_eq!(txvr.system_vdd().unwrap(), 3_150);
+    /// ```
+    pub fn system_vdd(&mut self) -> Result<u16> {
+        self.request(protocol::system_vdd())
+    }
 
-/// # Radio API Functions
-impl Rn2903 {
-    /// Set the modulation mode used by the radio for transmission and reception.
-    pub fn radio_set_modulation_mode(&mut self, mode: ModulationMode) -> Result<()> {
-        match mode {
-            ModulationMode::Fsk => self.transact_expecting(b"radio set mod fsk", b"ok"),
-            ModulationMode::LoRa => self.transact_expecting(b"radio set mod lora", b"ok"),
-        }
-    }
-
-    /// Open the receiver for the given timeout in symbols (for LoRa) or milliseconds
-    /// (for FSK), returning `Ok(Some(_))` if a valid packet is received or `Ok(None)` if
-    /// no packet is received before the timeout.
-    pub fn radio_rx(&mut self, timeout: u16) -> Result<Option<Vec<u8>>> {
-        let result = self.transact(&format!("radio rx {}", timeout).into_bytes())?;
-        match &result[..] {
-            b"ok" => (),
-            b"busy" => return Err(Error::TransceiverBusy),
-            v => return Err(Error::bad_response("ok | busy", bytes_to_string(v))),
-        };
-        let response = self.read_line()?;
-        match &response[0..9] {
-            b"radio_err" => Ok(None),
-            b"radio_rx " => {
-                let response_bytes: std::result::Result<Vec<u8>, _> = response[10..]
-                    .chunks(2)
-                    .map(bytes_to_string)
-                    .map(|b| u8::from_str_radix(&b, 16))
-                    .collect();
-                match response_bytes {
-                    Ok(v) => Ok(Some(v)),
-                    Err(_) => Err(Error::bad_response(
-                        "radio_rx <bytes>",
-                        bytes_to_string(&response),
-                    )),
-                }
-            }
-            _ => Err(Error::bad_response(
-                "radio_err | radio_rx <bytes>",
-                bytes_to_string(&response),
-            )),
-        }
-    }
-
-    pub fn radio_tx(&mut self, send: String) -> Result<Option<Vec<u8>>> {
-        let result = self.transact(&format!("radio tx {}", hex::encode(send)).into_bytes())?;
-        match &result[..] {
-            b"ok" => {
-                let sresult = self.read_line()?;
-                match &sresult[..] {
-                    b"radio_tx_ok" => return Ok(None),
-                    b"radio_err" => return Err(Error::TransmissionUnsuccessful),
-                    v => return Err(Error::bad_response("nok", bytes_to_string(v))),
-                }
-            }
-            b"invalid_param" => return Err(Error::InvalidParam),
-            b"busy" => return Err(Error::TransceiverBusy),
-            v => return Err(Error::bad_response("ok | busy", bytes_to_string(v))),
-        };
+    /// Queries the EUI-64 preprogrammed into the module, which is unique to it and makes
+    /// a good default DevEUI.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// let hweui = txvr.system_hweui().unwrap();
+    /// assert_eq!(hweui.to_string(), "0004A30B00F1E2D3");
+    /// ```
+    pub fn system_hweui(&mut self) -> Result<Eui64> {
+        self.request(protocol::system_hweui())
     }
-}
 
-/// # MAC API Functions
-impl Rn2903 {
-    /// Pauses the LoRaWAN MAC functionality on the device, returning the number of
-    /// milliseconds for which the MAC can remain paused without affecting LoRaWAN
-    /// functionality.
-    ///
-    /// This command can fail with `CannotPause`, meaning the device is operating in a
-    /// mode (like LoRaWAN Class C mode) in which pausing the MAC for any period of time
-    /// would result in degraded service.
-    pub fn mac_pause(&mut self) -> Result<u32> {
-        let val = bytes_to_string(&self.transact(b"mac pause")?);
-        let ms: u32 = match val.parse() {
-            Ok(v) => v,
-            Err(_) => return Err(Error::bad_response("<integer>", val)),
-        };
-        if ms == 0 {
-            Err(Error::CannotPause)
-        } else {
-            Ok(ms)
-        }
+    /// Puts the module to sleep for the given duration, in whole milliseconds, returning
+    /// once it wakes up and reports so.
+    ///
+    /// Durations shorter than 100 ms or longer than `u32::MAX` ms are rejected with
+    /// `Error::OutOfRange` without being sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// # use std::time::{Duration, Instant};
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// let started = Instant::now();
+    /// txvr.system_sleep(Duration::from_millis(100)).unwrap();
+    /// assert!(started.elapsed() >= Duration::from_millis(100));
+    /// assert!(txvr.system_sleep(Duration::from_millis(99)).is_err());
+    /// ```
+    pub fn system_sleep(&mut self, duration: Duration) -> Result<()> {
+        self.run(operation::Sleep::new(duration)?)
+    }
+
+    /// Puts the module to sleep for the given duration, as
+    /// [`::system_sleep()`](#method.system_sleep) does, without waiting for it to wake
+    /// up. Use [`::system_wake()`](#method.system_wake) to wake it, early or otherwise.
+    pub fn system_sleep_start(&mut self, duration: Duration) -> Result<()> {
+        let command = protocol::system_sleep(duration)?;
+        self.write_line(command.to_string().as_bytes())
     }
 
-    /// Resumes LoRaWAN MAC functionality on the device after being paused.
-    pub fn mac_resume(&mut self) -> Result<()> {
-        self.transact_expecting(b"mac resume", b"ok")
+    /// Wakes the module from sleep, early if need be, and waits for it to report so.
+    ///
+    /// If the module has not already reported waking up, this sends a break condition
+    /// followed by the 0x55 byte the module measures the baud rate from. Anything the
+    /// port delivers before and just after the break, which may be left over from
+    /// earlier or garbled by the break, is discarded first. This requires a `Transport`
+    /// which can [send a break](trait.Transport.html#method.send_break).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// # use std::time::{Duration, Instant};
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// let started = Instant::now();
+    /// txvr.system_sleep_start(Duration::from_secs(3600)).unwrap();
+    /// txvr.system_wake().unwrap();
+    /// assert!(started.elapsed() < Duration::from_secs(1));
+    /// assert!(txvr.system_version().unwrap().starts_with("RN2903"));
+    /// ```
+    pub fn system_wake(&mut self) -> Result<()> {
+        self.run(operation::Wake::new())
     }
 }
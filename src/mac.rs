@@ -0,0 +1,379 @@
+//! Types and functions for the LoRaWAN MAC built into the module.
+
+use crate::{operation, protocol};
+use crate::{Result, Rn2903, Transport};
+use core::fmt;
+
+/// A 64-bit extended unique identifier, like a DevEUI or AppEUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Eui64([u8; 8]);
+
+impl Eui64 {
+    /// Creates an `Eui64` from its bytes, most significant first.
+    pub fn new(bytes: [u8; 8]) -> Self {
+        Eui64(bytes)
+    }
+
+    /// Returns the bytes of the identifier, most significant first.
+    pub fn bytes(self) -> [u8; 8] {
+        self.0
+    }
+
+    /// Parses the 16 hexadecimal digits the module uses to represent an EUI.
+    pub(crate) fn from_hex(hex: &str) -> Option<Self> {
+        let mut bytes = [0; 8];
+        hex::decode_to_slice(hex, &mut bytes).ok()?;
+        Some(Eui64(bytes))
+    }
+}
+
+impl From<[u8; 8]> for Eui64 {
+    fn from(bytes: [u8; 8]) -> Self {
+        Eui64(bytes)
+    }
+}
+
+impl fmt::Display for Eui64 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&hex::encode_upper(self.0))
+    }
+}
+
+/// A 32-bit LoRaWAN device address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DevAddr(pub u32);
+
+impl fmt::Display for DevAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08X}", self.0)
+    }
+}
+
+/// A 128-bit AES key, like an AppKey, NwkSKey, or AppSKey.
+///
+/// The `Debug` representation does not include the key material.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AesKey([u8; 16]);
+
+impl AesKey {
+    /// Creates an `AesKey` from its bytes, most significant first.
+    pub fn new(bytes: [u8; 16]) -> Self {
+        AesKey(bytes)
+    }
+
+    /// Returns the bytes of the key, most significant first.
+    pub fn bytes(self) -> [u8; 16] {
+        self.0
+    }
+}
+
+impl From<[u8; 16]> for AesKey {
+    fn from(bytes: [u8; 16]) -> Self {
+        AesKey(bytes)
+    }
+}
+
+impl fmt::Debug for AesKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AesKey(..)")
+    }
+}
+
+/// Ways of joining a LoRaWAN network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinMode {
+    /// Over-the-air activation, using the DevEUI, AppEUI, and AppKey to negotiate
+    /// session keys with the network.
+    Otaa,
+    /// Activation by personalization, using a preset DevAddr, NwkSKey, and AppSKey.
+    Abp,
+}
+
+/// Data sent by the network to the module in one of the receive windows after an uplink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Downlink {
+    /// The LoRaWAN port the data was sent to.
+    pub port: u8,
+    /// The application payload, decrypted by the module.
+    pub payload: Vec<u8>,
+}
+
+impl Downlink {
+    /// Parses the `mac_rx <port> <data>` notification.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("mac_rx") {
+            return None;
+        }
+        let port = words.next()?.parse().ok()?;
+        let payload = match words.next() {
+            Some(data) => hex::decode(data).ok()?,
+            None => Vec::new(),
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Downlink { port, payload })
+    }
+}
+
+/// # MAC API Functions
+impl<T: Transport> Rn2903<T> {
+    /// Pauses the LoRaWAN MAC functionality on the device, returning the number of
+    /// milliseconds for which the MAC can remain paused without affecting LoRaWAN
+    /// functionality.
+    ///
+    /// This command can fail with `CannotPause`, meaning the device is operating in a
+    /// mode (like LoRaWAN Class C mode) in which pausing the MAC for any period of time
+    /// would result in degraded service.
+    pub fn mac_pause(&mut self) -> Result<u32> {
+        self.request(protocol::mac_pause())
+    }
+
+    /// Resumes LoRaWAN MAC functionality on the device after being paused.
+    pub fn mac_resume(&mut self) -> Result<()> {
+        self.request(protocol::mac_resume())
+    }
+
+    /// Sets the globally unique end-device identifier (DevEUI) used for over-the-air
+    /// activation.
+    pub fn mac_set_device_eui(&mut self, eui: Eui64) -> Result<()> {
+        self.request(protocol::mac_set_device_eui(eui))
+    }
+
+    /// Queries the end-device identifier (DevEUI).
+    pub fn mac_get_device_eui(&mut self) -> Result<Eui64> {
+        self.request(protocol::mac_get_device_eui())
+    }
+
+    /// Sets the application identifier (AppEUI, called JoinEUI in LoRaWAN 1.1) used for
+    /// over-the-air activation.
+    pub fn mac_set_app_eui(&mut self, eui: Eui64) -> Result<()> {
+        self.request(protocol::mac_set_app_eui(eui))
+    }
+
+    /// Queries the application identifier (AppEUI).
+    pub fn mac_get_app_eui(&mut self) -> Result<Eui64> {
+        self.request(protocol::mac_get_app_eui())
+    }
+
+    /// Sets the application key (AppKey) used for over-the-air activation.
+    pub fn mac_set_app_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_app_key(key))
+    }
+
+    /// Sets the device address (DevAddr) used for activation by personalization.
+    pub fn mac_set_device_address(&mut self, address: DevAddr) -> Result<()> {
+        self.request(protocol::mac_set_device_address(address))
+    }
+
+    /// Queries the device address (DevAddr), which is assigned by the network when
+    /// joining over the air.
+    pub fn mac_get_device_address(&mut self) -> Result<DevAddr> {
+        self.request(protocol::mac_get_device_address())
+    }
+
+    /// Sets the network session key (NwkSKey) used for activation by personalization.
+    pub fn mac_set_network_session_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_network_session_key(key))
+    }
+
+    /// Sets the application session key (AppSKey) used for activation by
+    /// personalization.
+    pub fn mac_set_app_session_key(&mut self, key: AesKey) -> Result<()> {
+        self.request(protocol::mac_set_app_session_key(key))
+    }
+
+    /// Joins a LoRaWAN network with the given activation mode.
+    ///
+    /// Over-the-air activation waits for the network to answer the join request, which
+    /// takes several seconds. Activation by personalization completes immediately. If the
+    /// network does not accept the join, this fails with `JoinDenied`; other failures
+    /// reported by the module have their own error variants, like `KeysNotInitialized`
+    /// or `NoFreeChannel`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{AesKey, Eui64, JoinMode, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// txvr.mac_set_device_eui(Eui64::new([0x00, 0x04, 0xA3, 0x0B, 0x00, 0x1A, 0x2B, 0x3C]))
+    ///     .unwrap();
+    /// txvr.mac_set_app_eui(Eui64::new([0x70, 0xB3, 0xD5, 0x7E, 0xD0, 0x00, 0x00, 0x01]))
+    ///     .unwrap();
+    /// txvr.mac_set_app_key(AesKey::new([0x2B; 16])).unwrap();
+    /// txvr.mac_join(JoinMode::Otaa).unwrap();
+    /// ```
+    pub fn mac_join(&mut self, mode: JoinMode) -> Result<()> {
+        self.run(operation::mac_join(mode))
+    }
+
+    /// Sends an uplink with the given payload to the given LoRaWAN port, which must be
+    /// between 1 and 223, returning any downlink the network sent in reply.
+    ///
+    /// A confirmed uplink is retransmitted until the network acknowledges it, and fails
+    /// with `MacTransmissionFailed` if it never does. The module must have joined a
+    /// network first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{AesKey, DevAddr, Downlink, JoinMode, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// txvr.mac_set_device_address(DevAddr(0x2601_1BDA)).unwrap();
+    /// txvr.mac_set_network_session_key(AesKey::new([0x11; 16])).unwrap();
+    /// txvr.mac_set_app_session_key(AesKey::new([0x22; 16])).unwrap();
+    /// txvr.mac_join(JoinMode::Abp).unwrap();
+    ///
+    /// assert_eq!(txvr.mac_tx(false, 1, &[0x01, 0x9A]).unwrap(), None);
+    ///
+    /// txvr.port().queue_downlink(10, &[0xFF]);
+    /// let downlink = txvr.mac_tx(true, 1, &[0x01, 0x9B]).unwrap();
+    /// assert_eq!(downlink, Some(Downlink { port: 10, payload: vec![0xFF] }));
+    /// ```
+    pub fn mac_tx(
+        &mut self,
+        confirmed: bool,
+        port: u8,
+        payload: &[u8],
+    ) -> Result<Option<Downlink>> {
+        self.run(operation::mac_tx(confirmed, port, payload)?)
+    }
+}
+
+/// # MAC Channel Functions
+///
+/// The LoRaWAN MAC transmits on the enabled channels of the module's plan: 16 channels
+/// on the RN2483, of which channels 3 to 15 must be given a frequency before use, and 72
+/// fixed channels on the RN2903. Channel indices the detected model does not have are
+/// rejected with `Error::OutOfRange` without being sent, as are frequencies outside its
+/// [regions](region/index.html). Settings are lost when the module is reset.
+///
+/// # Example
+///
+/// Using only sub-band 2 of the US915 plan, channels 8 to 15 and 65, as many gateways
+/// do.
+///
+/// ```
+/// # use rn2903::Rn2903;
+/// # use rn2903::sim::Simulator;
+/// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+/// txvr.mac_enable_only_subband(2).unwrap();
+/// assert!(!txvr.mac_get_channel_enabled(0).unwrap());
+/// assert!(txvr.mac_get_channel_enabled(8).unwrap());
+/// assert!(txvr.mac_get_channel_enabled(65).unwrap());
+/// assert_eq!(txvr.mac_get_channel_frequency(8).unwrap(), 903_900_000);
+/// assert_eq!(txvr.mac_get_channel_data_rate_range(65).unwrap(), (4, 4));
+/// assert!(txvr.mac_get_channel_enabled(72).is_err());
+/// assert!(txvr.mac_set_channel_frequency(8, 903_900_000).is_err());
+/// assert!(txvr.mac_set_channel_duty_cycle(8, 99).is_err());
+/// ```
+///
+/// Adding a channel on an RN2483.
+///
+/// ```
+/// # use rn2903::Rn2903;
+/// # use rn2903::sim::Simulator;
+/// let mut txvr = Rn2903::new(Simulator::rn2483()).unwrap();
+/// txvr.mac_set_channel_frequency(3, 867_100_000).unwrap();
+/// txvr.mac_set_channel_duty_cycle(3, 999).unwrap();
+/// txvr.mac_set_channel_data_rate_range(3, 0, 5).unwrap();
+/// txvr.mac_set_channel_enabled(3, true).unwrap();
+/// assert_eq!(txvr.mac_get_channel_duty_cycle(3).unwrap(), 999);
+/// assert!(txvr.mac_set_channel_frequency(4, 915_000_000).is_err());
+/// assert!(txvr.mac_set_channel_frequency(2, 868_500_000).is_err());
+/// assert!(txvr.mac_enable_only_subband(2).is_err());
+/// ```
+impl<T: Transport> Rn2903<T> {
+    /// Sets the frequency of a channel in Hz. Only channels 3 to 15 of the RN2483 can be
+    /// set; the others, and every channel of the RN2903, are rejected with
+    /// `Error::OutOfRange` without being sent.
+    pub fn mac_set_channel_frequency(&mut self, channel: u8, hz: u32) -> Result<()> {
+        self.request(protocol::mac_set_channel_frequency(
+            self.session.model,
+            channel,
+            hz,
+        )?)
+    }
+
+    /// Queries the frequency of a channel in Hz.
+    pub fn mac_get_channel_frequency(&mut self, channel: u8) -> Result<u32> {
+        self.request(protocol::mac_get_channel_frequency(
+            self.session.model,
+            channel,
+        )?)
+    }
+
+    /// Sets the duty cycle of a channel, which is `100 / (value + 1)` percent: 99 for 1%.
+    /// Only the RN2483 limits the duty cycle of its channels, so this is rejected with
+    /// `Error::OutOfRange` on the RN2903 without being sent.
+    pub fn mac_set_channel_duty_cycle(&mut self, channel: u8, value: u16) -> Result<()> {
+        self.request(protocol::mac_set_channel_duty_cycle(
+            self.session.model,
+            channel,
+            value,
+        )?)
+    }
+
+    /// Queries the duty cycle of a channel, as set with
+    /// [`::mac_set_channel_duty_cycle()`](#method.mac_set_channel_duty_cycle).
+    pub fn mac_get_channel_duty_cycle(&mut self, channel: u8) -> Result<u16> {
+        self.request(protocol::mac_get_channel_duty_cycle(
+            self.session.model,
+            channel,
+        )?)
+    }
+
+    /// Sets the lowest and highest data rate index used on a channel.
+    ///
+    /// Ranges which are empty or reach beyond index 15 are rejected with
+    /// `Error::OutOfRange`; the module rejects data rates its plan does not define.
+    pub fn mac_set_channel_data_rate_range(&mut self, channel: u8, min: u8, max: u8) -> Result<()> {
+        self.request(protocol::mac_set_channel_data_rate_range(
+            self.session.model,
+            channel,
+            min,
+            max,
+        )?)
+    }
+
+    /// Queries the lowest and highest data rate index used on a channel.
+    pub fn mac_get_channel_data_rate_range(&mut self, channel: u8) -> Result<(u8, u8)> {
+        self.request(protocol::mac_get_channel_data_rate_range(
+            self.session.model,
+            channel,
+        )?)
+    }
+
+    /// Enables or disables a channel.
+    pub fn mac_set_channel_enabled(&mut self, channel: u8, enabled: bool) -> Result<()> {
+        self.request(protocol::mac_set_channel_enabled(
+            self.session.model,
+            channel,
+            enabled,
+        )?)
+    }
+
+    /// Queries whether a channel is enabled.
+    pub fn mac_get_channel_enabled(&mut self, channel: u8) -> Result<bool> {
+        self.request(protocol::mac_get_channel_enabled(
+            self.session.model,
+            channel,
+        )?)
+    }
+
+    /// Enables the channels of the given sub-band of the US915 or AU915 plan, numbered
+    /// from 1 to 8, and disables all others, then queries every channel to check it took
+    /// effect.
+    ///
+    /// Sub-band `n` is the eight 125 kHz channels from `8 * (n - 1)` and the 500 kHz
+    /// channel `63 + n`. Other sub-bands, and any on the RN2483, are rejected with
+    /// `Error::OutOfRange`. Fails with `Error::ChannelVerifyFailed` if a channel does not
+    /// report the status it was set to.
+    pub fn mac_enable_only_subband(&mut self, subband: u8) -> Result<()> {
+        let model = self.session.model;
+        self.run(operation::EnableOnlySubband::new(model, subband)?)
+    }
+}
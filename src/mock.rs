@@ -0,0 +1,207 @@
+//! A scripted `Transport` for pinning the exact bytes exchanged with the module.
+//!
+//! Where the [`sim`](../sim/index.html) module behaves like a whole module, a
+//! [`MockTransport`](struct.MockTransport.html) knows nothing about the protocol: it is
+//! given an ordered list of command lines it should receive, each with the reply lines it
+//! should answer with, and panics with a description of the difference as soon as
+//! anything else is written.
+//!
+//! # Examples
+//!
+//! Checking the wire format of NVM writes and radio transmissions.
+//!
+//! ```
+//! # use rn2903::{NvmAddress, Rn2903};
+//! use rn2903::mock::MockTransport;
+//!
+//! let mut mock = MockTransport::new();
+//! mock.expect(b"sys set nvm 300 ab", &[b"ok"]);
+//! mock.expect(b"mac pause", &[b"4294967245"]);
+//! mock.expect(b"radio get mod", &[b"lora"]);
+//! mock.expect(b"radio get wdt", &[b"15000"]);
+//! mock.expect(b"radio tx 00ff", &[b"ok", b"radio_tx_ok"]);
+//!
+//! let mut txvr = Rn2903::new_unchecked(mock);
+//! txvr.system_set_nvm(NvmAddress::new(0x300), 0xAB).unwrap();
+//! txvr.mac_pause().unwrap();
+//! txvr.radio_transmit(&[0x00, 0xFF]).unwrap();
+//! txvr.port().assert_finished();
+//! ```
+
+use crate::{bytes_to_string, Transport};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::task::Waker;
+
+/// A command line the mock expects to receive, and the lines it replies with.
+#[derive(Debug, Clone)]
+struct Expectation {
+    command: Vec<u8>,
+    responses: Vec<Vec<u8>>,
+}
+
+/// A `Transport` which checks written commands against a script and replies with canned
+/// responses.
+///
+/// Reply lines are released one at a time: the next line only becomes readable once the
+/// previous one has been read completely, as with the asynchronous second replies of the
+/// real module. Reading when no reply is pending fails with `TimedOut`.
+///
+/// # Panics
+///
+/// Writing a command line which differs from the next expected one, or writing any
+/// command once the script is exhausted, panics with a message showing both lines and
+/// where they first differ.
+///
+/// ```should_panic
+/// # use rn2903::Rn2903;
+/// use rn2903::mock::MockTransport;
+///
+/// let mut mock = MockTransport::new();
+/// mock.expect(b"radio set mod lora", &[b"ok"]);
+/// let mut txvr = Rn2903::new_unchecked(mock);
+/// // Panics: expected 'radio set mod lora', got 'radio set mod fsk'.
+/// txvr.transact(b"radio set mod fsk");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    expectations: VecDeque<Expectation>,
+    input: Vec<u8>,
+    output: VecDeque<u8>,
+    pending: VecDeque<Vec<u8>>,
+    reader: Option<Waker>,
+}
+
+impl MockTransport {
+    /// Creates a mock which expects no commands at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a command line (without CRLF) to the script, along with the lines
+    /// (without CRLFs) the mock replies with when it is received.
+    pub fn expect(&mut self, command: &[u8], responses: &[&[u8]]) {
+        self.expectations.push_back(Expectation {
+            command: command.to_vec(),
+            responses: responses.iter().map(|r| r.to_vec()).collect(),
+        });
+    }
+
+    /// Queues a line (without CRLF) to be read after any replies already pending,
+    /// without any command being written first, like an unsolicited notification from
+    /// the module.
+    pub fn push_line(&mut self, line: &[u8]) {
+        self.pending.push_back(line.to_vec());
+        self.wake_reader();
+    }
+
+    /// Returns the number of scripted commands which have not been received yet.
+    pub fn remaining(&self) -> usize {
+        self.expectations.len()
+    }
+
+    /// Asserts that every scripted command has been received and every reply read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any part of the script has not been played out.
+    pub fn assert_finished(&self) {
+        if let Some(next) = self.expectations.front() {
+            panic!(
+                "MockTransport finished with {} command(s) never received, starting with '{}'",
+                self.expectations.len(),
+                bytes_to_string(&next.command)
+            );
+        }
+        if !self.output.is_empty() || !self.pending.is_empty() {
+            panic!(
+                "MockTransport finished with {} reply line(s) never read",
+                self.pending.len() + !self.output.is_empty() as usize
+            );
+        }
+    }
+
+    /// Checks a complete command line against the script, queueing its replies.
+    fn receive(&mut self, line: &[u8]) {
+        let expectation = match self.expectations.pop_front() {
+            Some(e) => e,
+            None => panic!(
+                "MockTransport received unexpected command '{}' after the script ended",
+                bytes_to_string(line)
+            ),
+        };
+        if expectation.command != line {
+            let position = expectation
+                .command
+                .iter()
+                .zip(line)
+                .position(|(e, a)| e != a)
+                .unwrap_or_else(|| expectation.command.len().min(line.len()));
+            panic!(
+                "MockTransport received the wrong command\n  expected: '{}'\n       got: '{}'\n            {}^ first difference at byte {}",
+                bytes_to_string(&expectation.command),
+                bytes_to_string(line),
+                " ".repeat(position),
+                position
+            );
+        }
+        self.pending.extend(expectation.responses);
+        self.wake_reader();
+    }
+
+    /// Wakes the task waiting to read, if there is one, as a line has been queued.
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output.is_empty() {
+            if let Some(line) = self.pending.pop_front() {
+                self.output.extend(line);
+                self.output.extend(b"\r\n");
+            }
+        }
+        if self.output.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "mock transport has no reply pending",
+            ));
+        }
+        let count = buf.len().min(self.output.len());
+        for (slot, byte) in buf.iter_mut().zip(self.output.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+        while let Some(end) = self.input.windows(2).position(|w| w == b"\r\n") {
+            let line: Vec<u8> = self.input.drain(..end + 2).take(end).collect();
+            self.receive(&line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {}
+
+#[cfg(feature = "async")]
+impl_async_in_memory!(MockTransport);
+
+#[cfg(feature = "async")]
+impl crate::asynchronous::InMemory for MockTransport {
+    fn wake_when_readable(&mut self, waker: Waker) {
+        self.reader = Some(waker);
+    }
+}
@@ -0,0 +1,86 @@
+//! Reading and writing the user area of nonvolatile memory in bulk.
+
+use crate::operation;
+use crate::{Error, NvmAddress, Result, Rn2903, Transport};
+
+/// The size, in bytes, of the user area of nonvolatile memory, from 0x300 to 0x3FF.
+pub const NVM_SIZE: usize = 256;
+
+/// How [`Rn2903::write_nvm_bytes()`](struct.Rn2903.html#method.write_nvm_bytes) writes
+/// each byte. By default, every byte is written once and not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NvmWriteOptions {
+    /// Read every byte back after writing it, failing with `Error::NvmVerifyFailed` if it
+    /// does not hold the value written.
+    pub verify: bool,
+    /// Read every byte before writing it, and leave bytes which already hold the value
+    /// alone, to limit wear on the EEPROM.
+    pub skip_unchanged: bool,
+}
+
+/// Returns the addresses of the `len` bytes starting at `start`, rejecting ranges which
+/// run past the end of the user area.
+pub(crate) fn nvm_addresses(
+    start: NvmAddress,
+    len: usize,
+) -> Result<impl Iterator<Item = NvmAddress>> {
+    let first = usize::from(start.inner());
+    if len > 0x400 - first {
+        return Err(Error::out_of_range("NVM range length", len));
+    }
+    Ok((first..first + len).map(|address| NvmAddress(address as u16)))
+}
+
+/// # NVM Functions
+impl<T: Transport> Rn2903<T> {
+    /// Reads `len` consecutive bytes of nonvolatile memory, starting at the given address.
+    ///
+    /// Ranges running past 0x3FF are rejected with `Error::OutOfRange` without anything
+    /// being read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{NvmAddress, Rn2903, NVM_SIZE};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// let all = txvr.read_nvm_range(NvmAddress::new(0x300), NVM_SIZE).unwrap();
+    /// assert_eq!(all, vec![0xFF; NVM_SIZE]);
+    /// assert!(txvr.read_nvm_range(NvmAddress::new(0x3FF), 2).is_err());
+    /// ```
+    pub fn read_nvm_range(&mut self, start: NvmAddress, len: usize) -> Result<Vec<u8>> {
+        self.run(operation::ReadNvm::new(start, len)?)
+    }
+
+    /// Writes the given bytes to consecutive addresses of nonvolatile memory, starting at
+    /// the given address, returning how many bytes were actually written.
+    ///
+    /// Ranges running past 0x3FF are rejected with `Error::OutOfRange` without anything
+    /// being written. If writing fails partway, the bytes before the failure remain
+    /// written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{NvmAddress, NvmWriteOptions, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// let start = NvmAddress::new(0x310);
+    /// let options = NvmWriteOptions {
+    ///     verify: true,
+    ///     skip_unchanged: true,
+    /// };
+    /// assert_eq!(txvr.write_nvm_bytes(start, &[1, 2, 3], options).unwrap(), 3);
+    /// // Only the byte which differs is written again.
+    /// assert_eq!(txvr.write_nvm_bytes(start, &[1, 9, 3], options).unwrap(), 1);
+    /// assert_eq!(txvr.read_nvm_range(start, 3).unwrap(), vec![1, 9, 3]);
+    /// ```
+    pub fn write_nvm_bytes(
+        &mut self,
+        start: NvmAddress,
+        bytes: &[u8],
+        options: NvmWriteOptions,
+    ) -> Result<usize> {
+        self.run(operation::WriteNvm::new(start, bytes, options)?)
+    }
+}
@@ -0,0 +1,1122 @@
+//! Operations which take more than one command, or more than one reply, as state
+//! machines without any I/O, driven the same way by both front-ends.
+//!
+//! An [`Operation`](trait.Operation.html) is resumed with the outcome of each
+//! [`Step`](enum.Step.html) it asks for until it is done. `Rn2903` performs the steps by
+//! blocking on its `Transport` and `AsyncRn2903` by awaiting its stream, so the commands
+//! sent, the order they are sent in, and the way replies are interpreted cannot drift
+//! apart between the two.
+
+use crate::nvm::nvm_addresses;
+use crate::protocol::{self, AirSettings, Event, RadioActivity, Request};
+use crate::radio::RadioState;
+use crate::region;
+use crate::{
+    Bandwidth, Downlink, Error, FirmwareVersion, GaussianBt, JoinMode, Model, ModulationMode,
+    NvmAddress, NvmWriteOptions, ReceivedPacket, Result, SpreadingFactor, Transmission,
+};
+use core::mem;
+use core::time::Duration;
+use std::time::{Instant, SystemTime};
+
+/// What the front-ends know about the module, shared by every operation.
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    /// Radio settings which have been set or queried, so need not be queried again.
+    pub(crate) radio: RadioState,
+    /// The model of the module, once it has been detected.
+    pub(crate) model: Option<Model>,
+}
+
+/// A single exchange with the module, performed by a front-end on behalf of an operation.
+///
+/// Each step produces the line it read, if any, which the operation is resumed with.
+pub(crate) enum Step {
+    /// Write the command line, then read the reply, waiting up to the response timeout.
+    Transact(String),
+    /// Write the command line without reading anything.
+    Write(String),
+    /// Read the next line, waiting up to the response timeout and the given time on top
+    /// of it, or forever if there is none.
+    ReadLine(Option<Duration>),
+    /// Read the next line, waiting up to the given time and producing none if it does not
+    /// arrive.
+    Poll(Duration),
+    /// Write the bytes as they are, without a line ending.
+    WriteBytes(Vec<u8>),
+    /// Send a break condition.
+    Break,
+    /// Discard everything received so far, and anything more until nothing has arrived
+    /// for the given time, or the response timeout has passed.
+    Drain(Duration),
+}
+
+impl Step {
+    /// Sends the command of the given request and reads the reply to it.
+    fn request<V>(request: &Request<V>) -> Self {
+        Step::Transact(request.command().to_string())
+    }
+}
+
+/// What an operation needs next: another step, or nothing because it is done.
+pub(crate) enum Flow<V> {
+    Step(Step),
+    Done(V),
+}
+
+impl<V> Flow<V> {
+    /// Maps the output of a finished operation.
+    fn map<U, F: FnOnce(V) -> U>(self, f: F) -> Flow<U> {
+        match self {
+            Flow::Step(step) => Flow::Step(step),
+            Flow::Done(output) => Flow::Done(f(output)),
+        }
+    }
+}
+
+/// An exchange with the module which takes several steps.
+pub(crate) trait Operation {
+    type Output;
+
+    /// Advances the operation with the line produced by the last step, or `None` when it
+    /// is first resumed or the step produced none. Once it has returned `Flow::Done` or an
+    /// error, it must not be resumed again.
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Self::Output>>;
+}
+
+/// Resumes a nested operation with the given reply, returning its step from the enclosing
+/// `resume` if it has one, or evaluating to its output once it is done.
+macro_rules! nested {
+    ($operation:expr, $session:expr, $reply:expr) => {
+        match $operation.resume($session, $reply)? {
+            Flow::Step(step) => return Ok(Flow::Step(step)),
+            Flow::Done(output) => output,
+        }
+    };
+}
+
+/// Sends the given request, keeping it in `pending`, wrapped as `wrap` does, until its
+/// reply arrives.
+fn send<P, V, O>(
+    pending: &mut P,
+    wrap: impl FnOnce(Request<V>) -> P,
+    request: Request<V>,
+) -> Result<Flow<O>> {
+    let step = Step::request(&request);
+    *pending = wrap(request);
+    Ok(Flow::Step(step))
+}
+
+/// The line an operation was resumed with. Only steps which read produce one, and an
+/// operation only asks for those when it expects a line, so this is never empty in
+/// practice.
+fn line(reply: Option<Vec<u8>>) -> Vec<u8> {
+    reply.unwrap_or_default()
+}
+
+/// A nested operation which keeps its output once done, so that an operation made of
+/// several in turn can resume each of them every time, and only the first unfinished one
+/// takes the reply.
+struct Nested<O: Operation> {
+    operation: O,
+    output: Option<O::Output>,
+}
+
+impl<O: Operation> Nested<O>
+where
+    O::Output: Clone,
+{
+    fn new(operation: O) -> Self {
+        Nested {
+            operation,
+            output: None,
+        }
+    }
+
+    /// Resumes the operation with the reply, unless it is already done.
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        reply: &mut Option<Vec<u8>>,
+    ) -> Result<Flow<O::Output>> {
+        if let Some(output) = &self.output {
+            return Ok(Flow::Done(output.clone()));
+        }
+        let flow = self.operation.resume(session, reply.take())?;
+        if let Flow::Done(output) = &flow {
+            self.output = Some(output.clone());
+        }
+        Ok(flow)
+    }
+}
+
+/// A single request, answered by a single line.
+pub(crate) struct Single<V>(Option<Request<V>>);
+
+impl<V> Single<V> {
+    pub(crate) fn new(request: Request<V>) -> Self {
+        Single(Some(request))
+    }
+}
+
+impl<V> Operation for Single<V> {
+    type Output = V;
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<V>> {
+        let request = self.0.take().expect("a request is resumed at most twice");
+        match reply {
+            None => {
+                if request.command().changes_radio() {
+                    session.radio = RadioState::default();
+                }
+                send(&mut self.0, Some, request)
+            }
+            Some(line) => request.interpret(&line).map(Flow::Done),
+        }
+    }
+}
+
+/// A request answered first with a line saying whether the command was accepted, then,
+/// once it completes, with an event such as `radio_tx_ok`.
+pub(crate) struct EventRequest<V> {
+    accepted: Single<()>,
+    accepted_at: Option<Instant>,
+    wait: Option<Duration>,
+    result: fn(&[u8]) -> Result<V>,
+}
+
+impl<V> EventRequest<V> {
+    /// Creates an operation sending the given request, then waiting up to the given
+    /// time on top of the response timeout, or forever if there is none, for the event
+    /// which the given function interprets.
+    pub(crate) fn new(
+        request: Request<()>,
+        wait: Option<Duration>,
+        result: fn(&[u8]) -> Result<V>,
+    ) -> Self {
+        EventRequest {
+            accepted: Single::new(request),
+            accepted_at: None,
+            wait,
+            result,
+        }
+    }
+}
+
+impl<V> Operation for EventRequest<V> {
+    type Output = V;
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<V>> {
+        if self.accepted_at.is_none() {
+            nested!(self.accepted, session, reply);
+            self.accepted_at = Some(Instant::now());
+            return Ok(Flow::Step(Step::ReadLine(self.wait)));
+        }
+        (self.result)(&line(reply)).map(Flow::Done)
+    }
+}
+
+/// Queries the firmware version and records the model it names.
+pub(crate) struct DetectModel(Single<String>);
+
+impl DetectModel {
+    pub(crate) fn new() -> Self {
+        DetectModel(Single::new(protocol::system_version()))
+    }
+}
+
+impl Operation for DetectModel {
+    type Output = FirmwareVersion;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        reply: Option<Vec<u8>>,
+    ) -> Result<Flow<FirmwareVersion>> {
+        let version = protocol::check_version(nested!(self.0, session, reply))?;
+        session.model = Some(version.model);
+        Ok(Flow::Done(version))
+    }
+}
+
+/// Puts the module to sleep and waits for it to wake up again.
+pub(crate) struct Sleep {
+    command: Option<String>,
+    duration: Duration,
+}
+
+impl Sleep {
+    /// Creates an operation sleeping for the given duration, rejecting durations the
+    /// module does not accept.
+    pub(crate) fn new(duration: Duration) -> Result<Self> {
+        Ok(Sleep {
+            command: Some(protocol::system_sleep(duration)?.to_string()),
+            duration,
+        })
+    }
+}
+
+impl Operation for Sleep {
+    type Output = ();
+
+    fn resume(&mut self, _: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<()>> {
+        match self.command.take() {
+            Some(command) => Ok(Flow::Step(Step::Write(command))),
+            None if reply.is_none() => Ok(Flow::Step(Step::ReadLine(Some(self.duration)))),
+            None => protocol::system_sleep_result(&line(reply)).map(Flow::Done),
+        }
+    }
+}
+
+/// Wakes the module from sleep with a break, unless it has already reported waking up.
+///
+/// Anything the port received before the break, and the garbage the break itself may
+/// produce, is read and discarded, so that the reply which follows the autobaud byte is
+/// the first line taken.
+pub(crate) struct Wake {
+    phase: WakePhase,
+}
+
+enum WakePhase {
+    Start,
+    Poll,
+    Break,
+    Drain,
+    Autobaud,
+    Woken,
+}
+
+impl Wake {
+    pub(crate) fn new() -> Self {
+        Wake {
+            phase: WakePhase::Start,
+        }
+    }
+}
+
+impl Operation for Wake {
+    type Output = ();
+
+    fn resume(&mut self, _: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<()>> {
+        let (phase, step) = match self.phase {
+            WakePhase::Start => (WakePhase::Poll, Step::Poll(protocol::WAKE_QUIET_TIME)),
+            // Nothing else is sent while the module sleeps, so `ok` means it is awake, and
+            // any other line is left over from before it went to sleep.
+            WakePhase::Poll => match reply {
+                None => (WakePhase::Break, Step::Break),
+                Some(line) if protocol::system_sleep_result(&line).is_ok() => {
+                    return Ok(Flow::Done(()))
+                }
+                Some(_) => (WakePhase::Poll, Step::Poll(protocol::WAKE_QUIET_TIME)),
+            },
+            WakePhase::Break => (WakePhase::Drain, Step::Drain(protocol::WAKE_QUIET_TIME)),
+            WakePhase::Drain => (
+                WakePhase::Autobaud,
+                Step::WriteBytes(vec![protocol::AUTOBAUD_BYTE]),
+            ),
+            WakePhase::Autobaud => (
+                WakePhase::Woken,
+                Step::ReadLine(Some(Duration::from_secs(0))),
+            ),
+            WakePhase::Woken => return protocol::system_sleep_result(&line(reply)).map(Flow::Done),
+        };
+        self.phase = phase;
+        Ok(Flow::Step(step))
+    }
+}
+
+/// Reads consecutive bytes of nonvolatile memory.
+pub(crate) struct ReadNvm {
+    addresses: Vec<NvmAddress>,
+    bytes: Vec<u8>,
+    pending: Option<Request<u8>>,
+}
+
+impl ReadNvm {
+    /// Creates an operation reading `len` bytes from the given address, rejecting ranges
+    /// which run past the end of the user area.
+    pub(crate) fn new(start: NvmAddress, len: usize) -> Result<Self> {
+        Ok(ReadNvm {
+            addresses: nvm_addresses(start, len)?.collect(),
+            bytes: Vec::with_capacity(len),
+            pending: None,
+        })
+    }
+}
+
+impl Operation for ReadNvm {
+    type Output = Vec<u8>;
+
+    fn resume(&mut self, _: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<Vec<u8>>> {
+        if let Some(request) = self.pending.take() {
+            self.bytes.push(request.interpret(&line(reply))?);
+        }
+        match self.addresses.get(self.bytes.len()) {
+            Some(&address) => send(&mut self.pending, Some, protocol::system_get_nvm(address)),
+            None => Ok(Flow::Done(mem::take(&mut self.bytes))),
+        }
+    }
+}
+
+/// Writes consecutive bytes of nonvolatile memory, as `NvmWriteOptions` asks.
+pub(crate) struct WriteNvm {
+    bytes: Vec<(NvmAddress, u8)>,
+    next: usize,
+    options: NvmWriteOptions,
+    written: usize,
+    pending: NvmRequest,
+}
+
+/// The request a `WriteNvm` is waiting on the reply to, along with the byte it is for.
+enum NvmRequest {
+    None,
+    Check(NvmAddress, u8, Request<u8>),
+    Write(NvmAddress, u8, Request<()>),
+    Verify(NvmAddress, u8, Request<u8>),
+}
+
+impl WriteNvm {
+    /// Creates an operation writing the given bytes from the given address, rejecting
+    /// ranges which run past the end of the user area.
+    pub(crate) fn new(start: NvmAddress, bytes: &[u8], options: NvmWriteOptions) -> Result<Self> {
+        Ok(WriteNvm {
+            bytes: nvm_addresses(start, bytes.len())?
+                .zip(bytes.iter().copied())
+                .collect(),
+            next: 0,
+            options,
+            written: 0,
+            pending: NvmRequest::None,
+        })
+    }
+
+    /// Sends the request to write the given byte.
+    fn write(&mut self, address: NvmAddress, value: u8) -> Result<Flow<usize>> {
+        let request = protocol::system_set_nvm(address, value);
+        send(
+            &mut self.pending,
+            |request| NvmRequest::Write(address, value, request),
+            request,
+        )
+    }
+}
+
+impl Operation for WriteNvm {
+    type Output = usize;
+
+    fn resume(&mut self, _: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<usize>> {
+        match mem::replace(&mut self.pending, NvmRequest::None) {
+            NvmRequest::None => (),
+            NvmRequest::Check(address, value, request) => {
+                if request.interpret(&line(reply))? != value {
+                    return self.write(address, value);
+                }
+            }
+            NvmRequest::Write(address, value, request) => {
+                request.interpret(&line(reply))?;
+                self.written += 1;
+                if self.options.verify {
+                    let request = protocol::system_get_nvm(address);
+                    return send(
+                        &mut self.pending,
+                        |request| NvmRequest::Verify(address, value, request),
+                        request,
+                    );
+                }
+            }
+            NvmRequest::Verify(address, value, request) => {
+                let found = request.interpret(&line(reply))?;
+                if found != value {
+                    return Err(Error::NvmVerifyFailed {
+                        address: address.inner(),
+                        expected: value,
+                        found,
+                    });
+                }
+            }
+        }
+        let (address, value) = match self.bytes.get(self.next) {
+            Some(&byte) => byte,
+            None => return Ok(Flow::Done(self.written)),
+        };
+        self.next += 1;
+        if self.options.skip_unchanged {
+            let request = protocol::system_get_nvm(address);
+            send(
+                &mut self.pending,
+                |request| NvmRequest::Check(address, value, request),
+                request,
+            )
+        } else {
+            self.write(address, value)
+        }
+    }
+}
+
+/// Sets the modulation mode, along with the Gaussian filter in FSK mode.
+pub(crate) struct SetModulation {
+    mode: ModulationMode,
+    pending: ModulationRequest<()>,
+}
+
+impl SetModulation {
+    pub(crate) fn new(mode: ModulationMode) -> Self {
+        SetModulation {
+            mode,
+            pending: ModulationRequest::None,
+        }
+    }
+}
+
+impl Operation for SetModulation {
+    type Output = ();
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<()>> {
+        match mem::replace(&mut self.pending, ModulationRequest::None) {
+            ModulationRequest::None => {
+                session.radio.modulation = None;
+                let request = protocol::radio_set_modulation(self.mode);
+                return send(&mut self.pending, ModulationRequest::Mode, request);
+            }
+            ModulationRequest::Mode(request) => {
+                request.interpret(&line(reply))?;
+                let bt = match self.mode {
+                    ModulationMode::Fsk => Some(GaussianBt::None),
+                    ModulationMode::Gfsk(bt) => Some(bt),
+                    ModulationMode::LoRa => None,
+                };
+                if let Some(bt) = bt {
+                    let request = protocol::radio_set_gaussian_bt(bt);
+                    return send(&mut self.pending, ModulationRequest::GaussianBt, request);
+                }
+            }
+            ModulationRequest::GaussianBt(request) => request.interpret(&line(reply))?,
+        }
+        session.radio.modulation = Some(self.mode);
+        Ok(Flow::Done(()))
+    }
+}
+
+/// The request a modulation operation is waiting on the reply to: the one setting or
+/// querying the mode, or the one for the Gaussian filter which follows in FSK mode.
+enum ModulationRequest<M, B = M> {
+    None,
+    Mode(Request<M>),
+    GaussianBt(Request<B>),
+}
+
+/// Queries the modulation mode, along with the Gaussian filter in FSK mode, and records
+/// it.
+pub(crate) struct GetModulation {
+    use_known: bool,
+    pending: ModulationRequest<bool, GaussianBt>,
+}
+
+impl GetModulation {
+    /// Creates an operation which always queries the module.
+    pub(crate) fn new() -> Self {
+        GetModulation {
+            use_known: false,
+            pending: ModulationRequest::None,
+        }
+    }
+
+    /// Creates an operation which queries the module only if the mode is not yet known.
+    pub(crate) fn unless_known() -> Self {
+        GetModulation {
+            use_known: true,
+            ..GetModulation::new()
+        }
+    }
+}
+
+impl Operation for GetModulation {
+    type Output = ModulationMode;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        reply: Option<Vec<u8>>,
+    ) -> Result<Flow<ModulationMode>> {
+        let mode = match mem::replace(&mut self.pending, ModulationRequest::None) {
+            ModulationRequest::None => match session.radio.modulation {
+                Some(mode) if self.use_known => return Ok(Flow::Done(mode)),
+                _ => {
+                    let request = protocol::radio_get_modulation();
+                    return send(&mut self.pending, ModulationRequest::Mode, request);
+                }
+            },
+            ModulationRequest::Mode(request) => {
+                if request.interpret(&line(reply))? {
+                    let request = protocol::radio_get_gaussian_bt();
+                    return send(&mut self.pending, ModulationRequest::GaussianBt, request);
+                }
+                protocol::modulation_mode(false, GaussianBt::None)
+            }
+            ModulationRequest::GaussianBt(request) => {
+                protocol::modulation_mode(true, request.interpret(&line(reply))?)
+            }
+        };
+        session.radio.modulation = Some(mode);
+        Ok(Flow::Done(mode))
+    }
+}
+
+/// Where a radio setting is remembered in the `RadioState`.
+type Remembered<V> = fn(&mut RadioState) -> &mut Option<V>;
+
+/// Changes a radio setting, remembering the new value once the module has accepted it.
+pub(crate) struct SetRadio<V> {
+    request: Single<()>,
+    value: V,
+    setting: Remembered<V>,
+    sent: bool,
+}
+
+impl<V> SetRadio<V> {
+    fn new(request: Request<()>, value: V, setting: Remembered<V>) -> Self {
+        SetRadio {
+            request: Single::new(request),
+            value,
+            setting,
+            sent: false,
+        }
+    }
+}
+
+impl<V: Copy> Operation for SetRadio<V> {
+    type Output = ();
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<()>> {
+        if !self.sent {
+            // Should the command fail, the setting is no longer known for certain.
+            *(self.setting)(&mut session.radio) = None;
+            self.sent = true;
+        }
+        nested!(self.request, session, reply);
+        *(self.setting)(&mut session.radio) = Some(self.value);
+        Ok(Flow::Done(()))
+    }
+}
+
+/// Queries a radio setting and remembers it, or, if created with `::unless_known()`,
+/// takes the remembered value instead if there is one.
+pub(crate) struct GetRadio<V> {
+    request: Single<V>,
+    setting: Remembered<V>,
+    query: bool,
+}
+
+impl<V> GetRadio<V> {
+    fn new(request: Request<V>, setting: Remembered<V>) -> Self {
+        GetRadio {
+            request: Single::new(request),
+            setting,
+            query: true,
+        }
+    }
+
+    /// Only queries the setting if it is not remembered.
+    pub(crate) fn unless_known(self) -> Self {
+        GetRadio {
+            query: false,
+            ..self
+        }
+    }
+}
+
+impl<V: Copy> Operation for GetRadio<V> {
+    type Output = V;
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<V>> {
+        if !self.query {
+            if let Some(value) = *(self.setting)(&mut session.radio) {
+                return Ok(Flow::Done(value));
+            }
+        }
+        let value = nested!(self.request, session, reply);
+        *(self.setting)(&mut session.radio) = Some(value);
+        Ok(Flow::Done(value))
+    }
+}
+
+pub(crate) fn set_spreading_factor(sf: SpreadingFactor) -> SetRadio<SpreadingFactor> {
+    SetRadio::new(protocol::radio_set_spreading_factor(sf), sf, |radio| {
+        &mut radio.spreading_factor
+    })
+}
+
+pub(crate) fn get_spreading_factor() -> GetRadio<SpreadingFactor> {
+    GetRadio::new(protocol::radio_get_spreading_factor(), |radio| {
+        &mut radio.spreading_factor
+    })
+}
+
+pub(crate) fn set_bandwidth(bw: Bandwidth) -> SetRadio<Bandwidth> {
+    SetRadio::new(protocol::radio_set_bandwidth(bw), bw, |radio| {
+        &mut radio.bandwidth
+    })
+}
+
+pub(crate) fn get_bandwidth() -> GetRadio<Bandwidth> {
+    GetRadio::new(protocol::radio_get_bandwidth(), |radio| {
+        &mut radio.bandwidth
+    })
+}
+
+/// Sets the carrier frequency, rejecting frequencies outside the model's regions.
+pub(crate) fn set_frequency(model: Option<Model>, hz: u32) -> Result<SetRadio<u32>> {
+    Ok(SetRadio::new(
+        protocol::radio_set_frequency(model, hz)?,
+        hz,
+        |radio| &mut radio.frequency,
+    ))
+}
+
+pub(crate) fn get_frequency() -> GetRadio<u32> {
+    GetRadio::new(protocol::radio_get_frequency(), |radio| {
+        &mut radio.frequency
+    })
+}
+
+pub(crate) fn set_watchdog_timeout(ms: u32) -> SetRadio<u32> {
+    SetRadio::new(protocol::radio_set_watchdog_timeout(ms), ms, |radio| {
+        &mut radio.watchdog_timeout
+    })
+}
+
+pub(crate) fn get_watchdog_timeout() -> GetRadio<u32> {
+    GetRadio::new(protocol::radio_get_watchdog_timeout(), |radio| {
+        &mut radio.watchdog_timeout
+    })
+}
+
+/// Looks up how long to wait for the radio to report on a reception or transmission,
+/// querying the watchdog timeout only if it is not yet known.
+pub(crate) struct RadioWait(GetRadio<u32>);
+
+impl RadioWait {
+    pub(crate) fn new() -> Self {
+        RadioWait(get_watchdog_timeout().unless_known())
+    }
+}
+
+impl Operation for RadioWait {
+    type Output = Option<Duration>;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Option<Duration>>> {
+        let ms = nested!(self.0, session, reply);
+        Ok(Flow::Done(protocol::radio_wait(ms)))
+    }
+}
+
+/// Looks up how long to wait for the radio to report on a reception or transmission:
+/// as long as the watchdog allows, or, with the watchdog disabled, as long as the
+/// activity can take at the current radio settings, which are queried if need be.
+pub(crate) struct ActivityWait {
+    activity: RadioActivity,
+    watchdog: Nested<RadioWait>,
+    modulation: Nested<GetModulation>,
+    spreading_factor: Nested<GetRadio<SpreadingFactor>>,
+    bandwidth: Nested<GetRadio<Bandwidth>>,
+    bitrate: Nested<Single<u32>>,
+    preamble: Nested<Single<u16>>,
+}
+
+impl ActivityWait {
+    pub(crate) fn new(activity: RadioActivity) -> Self {
+        ActivityWait {
+            activity,
+            watchdog: Nested::new(RadioWait::new()),
+            modulation: Nested::new(GetModulation::unless_known()),
+            spreading_factor: Nested::new(get_spreading_factor().unless_known()),
+            bandwidth: Nested::new(get_bandwidth().unless_known()),
+            bitrate: Nested::new(Single::new(protocol::radio_get_fsk_bitrate())),
+            preamble: Nested::new(Single::new(protocol::radio_get_preamble_length())),
+        }
+    }
+}
+
+impl Operation for ActivityWait {
+    type Output = Option<Duration>;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        mut reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Option<Duration>>> {
+        if let Some(wait) = nested!(self.watchdog, session, &mut reply) {
+            return Ok(Flow::Done(Some(wait)));
+        }
+        if let RadioActivity::Receive(0) = self.activity {
+            return Ok(Flow::Done(None));
+        }
+        let settings = match nested!(self.modulation, session, &mut reply) {
+            ModulationMode::LoRa => AirSettings::LoRa {
+                spreading_factor: nested!(self.spreading_factor, session, &mut reply),
+                bandwidth: nested!(self.bandwidth, session, &mut reply),
+                preamble: nested!(self.preamble, session, &mut reply),
+            },
+            ModulationMode::Fsk | ModulationMode::Gfsk(_) => AirSettings::Fsk {
+                bitrate: nested!(self.bitrate, session, &mut reply),
+                preamble: nested!(self.preamble, session, &mut reply),
+            },
+        };
+        Ok(Flow::Done(protocol::radio_activity_wait(
+            &self.activity,
+            &settings,
+        )))
+    }
+}
+
+/// Opens the receiver for the given timeout and returns the packet received, if any.
+pub(crate) struct RadioRx {
+    timeout: u16,
+    wait: ActivityWait,
+    rx: Option<EventRequest<Option<Vec<u8>>>>,
+}
+
+impl RadioRx {
+    pub(crate) fn new(timeout: u16) -> Self {
+        RadioRx {
+            timeout,
+            wait: ActivityWait::new(RadioActivity::Receive(timeout)),
+            rx: None,
+        }
+    }
+}
+
+impl Operation for RadioRx {
+    type Output = Option<Vec<u8>>;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        mut reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Option<Vec<u8>>>> {
+        if self.rx.is_none() {
+            let wait = nested!(self.wait, session, reply.take());
+            self.rx = Some(EventRequest::new(
+                protocol::radio_rx(self.timeout),
+                wait,
+                protocol::radio_rx_result,
+            ));
+        }
+        let rx = self.rx.as_mut().expect("created above");
+        rx.resume(session, reply)
+    }
+}
+
+/// Closes the receiver, returning the payload of a packet whose reception finished as it
+/// was being closed, if there was one.
+///
+/// The module may report the outcome of the reception, as `radio_rx` or `radio_err`, just
+/// before or just after it acknowledges `radio rxstop`. Either is consumed, so that it is
+/// not mistaken for the reply to the next command.
+pub(crate) struct RadioRxStop {
+    stop: Option<Request<()>>,
+    phase: RxStopPhase,
+    outcome: Option<Option<Vec<u8>>>,
+}
+
+enum RxStopPhase {
+    /// `radio rxstop` has yet to be sent.
+    Start,
+    /// Waiting for the reply to it, which the outcome of the reception may precede.
+    Stopping,
+    /// Closed; waiting briefly for the outcome, if it did not precede the reply.
+    Stopped,
+}
+
+impl RadioRxStop {
+    pub(crate) fn new() -> Self {
+        RadioRxStop {
+            stop: None,
+            phase: RxStopPhase::Start,
+            outcome: None,
+        }
+    }
+}
+
+impl Operation for RadioRxStop {
+    type Output = Option<Vec<u8>>;
+
+    fn resume(
+        &mut self,
+        _session: &mut Session,
+        reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Option<Vec<u8>>>> {
+        match self.phase {
+            RxStopPhase::Start => {
+                self.phase = RxStopPhase::Stopping;
+                send(&mut self.stop, Some, protocol::radio_rx_stop())
+            }
+            RxStopPhase::Stopping => {
+                let line = line(reply);
+                if self.outcome.is_none() {
+                    if let Some(Event::RadioRx(_)) | Some(Event::RadioErr) = Event::parse(&line) {
+                        self.outcome = Some(protocol::radio_rx_result(&line)?);
+                        return Ok(Flow::Step(Step::ReadLine(Some(Duration::from_secs(0)))));
+                    }
+                }
+                let stop = self.stop.take().expect("sent before its reply");
+                stop.interpret(&line)?;
+                match self.outcome.take() {
+                    Some(payload) => Ok(Flow::Done(payload)),
+                    None => {
+                        self.phase = RxStopPhase::Stopped;
+                        Ok(Flow::Step(Step::Poll(protocol::RX_STOP_QUIET_TIME)))
+                    }
+                }
+            }
+            RxStopPhase::Stopped => match reply {
+                Some(line) => protocol::radio_rx_result(&line).map(Flow::Done),
+                None => Ok(Flow::Done(None)),
+            },
+        }
+    }
+}
+
+/// Transmits a payload with the radio, in the current modulation mode.
+pub(crate) struct RadioTransmit {
+    payload: Vec<u8>,
+    modulation: GetModulation,
+    mode: Option<ModulationMode>,
+    wait: ActivityWait,
+    transmission: Option<EventRequest<()>>,
+}
+
+impl RadioTransmit {
+    pub(crate) fn new(payload: &[u8]) -> Self {
+        RadioTransmit {
+            payload: payload.to_vec(),
+            modulation: GetModulation::unless_known(),
+            mode: None,
+            wait: ActivityWait::new(RadioActivity::Transmit(payload.len())),
+            transmission: None,
+        }
+    }
+}
+
+impl Operation for RadioTransmit {
+    type Output = Transmission;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        mut reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Transmission>> {
+        if self.transmission.is_none() {
+            let modulation = match self.mode {
+                Some(mode) => mode,
+                None => {
+                    let mode = nested!(self.modulation, session, reply.take());
+                    self.mode = Some(mode);
+                    mode
+                }
+            };
+            // Payloads the mode does not allow are rejected before anything else is sent.
+            let request = protocol::radio_tx(&self.payload, modulation)?;
+            let wait = nested!(self.wait, session, reply.take());
+            self.transmission = Some(EventRequest::new(request, wait, protocol::radio_tx_result));
+        }
+        let transmission = self.transmission.as_mut().expect("created above");
+        nested!(transmission, session, reply);
+        Ok(Flow::Done(Transmission {
+            started: transmission.accepted_at.expect("the command was accepted"),
+            finished: Instant::now(),
+        }))
+    }
+}
+
+/// Gathers the signal quality and radio settings for a packet which has just been
+/// received, querying only the settings which are not already known.
+pub(crate) struct PacketInfo {
+    payload: Vec<u8>,
+    received_at: SystemTime,
+    modulation: Nested<GetModulation>,
+    snr: Nested<Single<i8>>,
+    rssi: Nested<Single<Option<i16>>>,
+    frequency: Nested<GetRadio<u32>>,
+    spreading_factor: Nested<GetRadio<SpreadingFactor>>,
+    bandwidth: Nested<GetRadio<Bandwidth>>,
+}
+
+impl PacketInfo {
+    pub(crate) fn new(payload: Vec<u8>, received_at: SystemTime) -> Self {
+        PacketInfo {
+            payload,
+            received_at,
+            modulation: Nested::new(GetModulation::unless_known()),
+            snr: Nested::new(Single::new(protocol::radio_get_snr())),
+            rssi: Nested::new(Single::new(protocol::radio_get_packet_rssi())),
+            frequency: Nested::new(get_frequency().unless_known()),
+            spreading_factor: Nested::new(get_spreading_factor().unless_known()),
+            bandwidth: Nested::new(get_bandwidth().unless_known()),
+        }
+    }
+}
+
+impl Operation for PacketInfo {
+    type Output = ReceivedPacket;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        mut reply: Option<Vec<u8>>,
+    ) -> Result<Flow<ReceivedPacket>> {
+        let modulation = nested!(self.modulation, session, &mut reply);
+        let lora = modulation == ModulationMode::LoRa;
+        let snr = if lora {
+            Some(nested!(self.snr, session, &mut reply))
+        } else {
+            None
+        };
+        let rssi = nested!(self.rssi, session, &mut reply);
+        let frequency = nested!(self.frequency, session, &mut reply);
+        let (spreading_factor, bandwidth) = if lora {
+            let sf = nested!(self.spreading_factor, session, &mut reply);
+            let bw = nested!(self.bandwidth, session, &mut reply);
+            (Some(sf), Some(bw))
+        } else {
+            (None, None)
+        };
+        Ok(Flow::Done(ReceivedPacket {
+            payload: mem::take(&mut self.payload),
+            snr,
+            rssi,
+            frequency,
+            modulation,
+            spreading_factor,
+            bandwidth,
+            received_at: self.received_at,
+        }))
+    }
+}
+
+/// Joins a LoRaWAN network, waiting for the network to accept the join.
+pub(crate) fn mac_join(mode: JoinMode) -> EventRequest<()> {
+    EventRequest::new(
+        protocol::mac_join(mode),
+        Some(protocol::MAC_JOIN_WAIT),
+        protocol::mac_join_result,
+    )
+}
+
+/// Sends a LoRaWAN uplink and returns any downlink sent in reply, rejecting ports and
+/// payloads the module does not accept.
+pub(crate) fn mac_tx(
+    confirmed: bool,
+    port: u8,
+    payload: &[u8],
+) -> Result<EventRequest<Option<Downlink>>> {
+    Ok(EventRequest::new(
+        protocol::mac_tx(confirmed, port, payload)?,
+        Some(protocol::mac_tx_wait(confirmed)),
+        protocol::mac_tx_result,
+    ))
+}
+
+/// Opens the receiver for the given timeout and returns the packet received, if any,
+/// along with its signal quality and radio settings.
+pub(crate) struct RadioReceive {
+    rx: RadioRx,
+    info: Option<PacketInfo>,
+}
+
+impl RadioReceive {
+    pub(crate) fn new(timeout: u16) -> Self {
+        RadioReceive {
+            rx: RadioRx::new(timeout),
+            info: None,
+        }
+    }
+}
+
+impl Operation for RadioReceive {
+    type Output = Option<ReceivedPacket>;
+
+    fn resume(
+        &mut self,
+        session: &mut Session,
+        mut reply: Option<Vec<u8>>,
+    ) -> Result<Flow<Option<ReceivedPacket>>> {
+        if self.info.is_none() {
+            match nested!(self.rx, session, reply.take()) {
+                Some(payload) => self.info = Some(PacketInfo::new(payload, SystemTime::now())),
+                None => return Ok(Flow::Done(None)),
+            }
+        }
+        let info = self.info.as_mut().expect("created above");
+        info.resume(session, reply).map(|flow| flow.map(Some))
+    }
+}
+
+/// Sets the enabled status of every channel of the 915 MHz plans to whether it belongs to
+/// a sub-band, then queries every channel to check it took effect.
+pub(crate) struct EnableOnlySubband {
+    channels: Vec<(u8, bool)>,
+    next: usize,
+    pending: ChannelRequest,
+}
+
+/// The request an `EnableOnlySubband` is waiting on the reply to.
+enum ChannelRequest {
+    None,
+    Set(Request<()>),
+    Check(u8, bool, Request<bool>),
+}
+
+impl EnableOnlySubband {
+    /// Creates an operation enabling only the given sub-band, rejecting sub-bands the
+    /// model does not have.
+    pub(crate) fn new(model: Option<Model>, subband: u8) -> Result<Self> {
+        Ok(EnableOnlySubband {
+            channels: region::subband_channels(model, subband)?.collect(),
+            next: 0,
+            pending: ChannelRequest::None,
+        })
+    }
+}
+
+impl Operation for EnableOnlySubband {
+    type Output = ();
+
+    fn resume(&mut self, session: &mut Session, reply: Option<Vec<u8>>) -> Result<Flow<()>> {
+        match mem::replace(&mut self.pending, ChannelRequest::None) {
+            ChannelRequest::None => (),
+            ChannelRequest::Set(request) => request.interpret(&line(reply))?,
+            ChannelRequest::Check(channel, enabled, request) => {
+                if request.interpret(&line(reply))? != enabled {
+                    return Err(Error::ChannelVerifyFailed { channel, enabled });
+                }
+            }
+        }
+        let count = self.channels.len();
+        let step = self.next;
+        self.next += 1;
+        if step < count {
+            let (channel, enabled) = self.channels[step];
+            let request = protocol::mac_set_channel_enabled(session.model, channel, enabled)?;
+            send(&mut self.pending, ChannelRequest::Set, request)
+        } else if step < 2 * count {
+            let (channel, enabled) = self.channels[step - count];
+            let request = protocol::mac_get_channel_enabled(session.model, channel)?;
+            send(
+                &mut self.pending,
+                |request| ChannelRequest::Check(channel, enabled, request),
+                request,
+            )
+        } else {
+            Ok(Flow::Done(()))
+        }
+    }
+}
@@ -0,0 +1,1694 @@
+//! The module's serial protocol, without any I/O: commands as typed values which format
+//! themselves as the exact ASCII lines the module expects, and parsers for the lines it
+//! sends back.
+//!
+//! Every command is answered with a single line, a [`Response`](enum.Response.html). Some
+//! commands, like `radio rx` or `mac join`, are later followed by a second line reporting
+//! their outcome, an [`Event`](enum.Event.html). Both [`Rn2903`](../struct.Rn2903.html)
+//! and the asynchronous front-end are built on this module, so it can also be used to
+//! drive the module over any other kind of I/O, or to script test doubles.
+//!
+//! # Examples
+//!
+//! ```
+//! use rn2903::protocol::{Command, Event, RadioSetting, Response};
+//! use rn2903::{NvmAddress, SpreadingFactor};
+//!
+//! let command = Command::RadioSet(RadioSetting::SpreadingFactor(SpreadingFactor::Sf9));
+//! assert_eq!(command.to_string(), "radio set sf sf9");
+//! assert_eq!(
+//!     Command::SysSetNvm(NvmAddress::new(0x300), 0xAB).encode(),
+//!     b"sys set nvm 300 ab\r\n"
+//! );
+//!
+//! assert_eq!(Response::parse(b"invalid_param"), Response::InvalidParam);
+//! assert_eq!(Response::parse(b"sf9"), Response::Value("sf9".to_string()));
+//! assert_eq!(Event::parse(b"radio_rx  CAFE"), Some(Event::RadioRx(vec![0xCA, 0xFE])));
+//! assert_eq!(Event::RadioErr.to_string(), "radio_err");
+//! ```
+//!
+//! # Robustness
+//!
+//! The parsers accept any bytes at all, never panicking: lines which are not valid UTF-8,
+//! are truncated, or carry stray whitespace are reported as unexpected replies rather
+//! than crashing the host. Leading and trailing whitespace is ignored, and the words of a
+//! line may be separated by any amount of it, as the spacing differs between firmware
+//! versions.
+
+use crate::mac::Downlink;
+use crate::radio::{on_off, parse_on_off};
+use crate::region;
+use crate::{
+    bytes_to_string, AesKey, Bandwidth, CodingRate, DevAddr, Error, Eui64, FirmwareVersion,
+    FskBandwidth, GaussianBt, JoinMode, Model, ModulationMode, NvmAddress, Pin, PinMode, Result,
+    SpreadingFactor,
+};
+use core::fmt;
+use core::ops::RangeInclusive;
+use core::time::Duration;
+
+/// The highest LoRaWAN data rate index accepted by the module.
+const MAX_DATA_RATE: u8 = 15;
+
+/// The first channel of the RN2483 whose frequency can be set; the ones below it are
+/// fixed by the plan, as are all of the RN2903's.
+const FIRST_SETTABLE_CHANNEL: u8 = 3;
+
+/// The FSK bit rates, in bits per second, accepted by the module.
+const BITRATE_RANGE: RangeInclusive<u32> = 1..=300_000;
+
+/// The FSK frequency deviations, in Hz, accepted by the module.
+const FREQUENCY_DEVIATION_RANGE: RangeInclusive<u32> = 0..=200_000;
+
+/// The longest FSK sync word, in bytes, accepted by the module.
+const MAX_FSK_SYNC_WORD_LEN: usize = 8;
+
+/// The longest payload, in bytes, the module transmits in LoRa mode, at any spreading factor
+/// and bandwidth: the radio's payload length register is a single byte. The smaller
+/// limits LoRaWAN places on slow data rates only apply to the MAC, which enforces them
+/// itself with `invalid_data_len`.
+const MAX_LORA_PAYLOAD_LEN: usize = 255;
+
+/// The longest payload, in bytes, the module transmits in FSK mode.
+const MAX_FSK_PAYLOAD_LEN: usize = 64;
+
+/// The longest a LoRaWAN frame can take to send or receive, rounded up. The plans allow
+/// at most a 64-byte PHY payload at their slowest data rate, SF12 at 125 kHz, which with
+/// the MAC's 4/5 coding rate and 8-symbol preamble is 85.25 symbols of 32.768 ms, or
+/// 2.79 s. Faster data rates allow longer payloads, but none takes as long.
+const MAX_LORAWAN_AIRTIME: Duration = Duration::from_secs(3);
+
+/// The time after a join request at which the second join accept window closes, rounded
+/// up from the 6 seconds of JOIN_ACCEPT_DELAY2.
+const JOIN_ACCEPT_DELAY2: Duration = Duration::from_secs(7);
+
+/// The time after an uplink at which the second receive window closes, rounded up from
+/// the 2 seconds of RECEIVE_DELAY2.
+const RECEIVE_DELAY2: Duration = Duration::from_secs(3);
+
+/// The longest the MAC waits after the second receive window before retransmitting an
+/// unacknowledged confirmed uplink: the upper end of ACK_TIMEOUT's 2 ± 1 seconds.
+const ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The number of times a confirmed uplink is sent before giving up, with the module's
+/// default of 7 retransmissions.
+const CONFIRMED_ATTEMPTS: u32 = 8;
+
+/// The durations, in milliseconds, the module accepts for `sys sleep`.
+const SLEEP_RANGE: RangeInclusive<u128> = 100..=u32::MAX as u128;
+
+/// The largest value read from an analog pin by the 10-bit converter.
+const MAX_ANALOG_VALUE: u16 = 1023;
+
+/// The LoRaWAN ports on which application data may be sent.
+const APPLICATION_PORTS: RangeInclusive<u8> = 1..=223;
+
+/// A command understood by the module.
+///
+/// The `Display` implementation writes the command line exactly as it is sent, without
+/// the terminating CRLF. No validation is performed; values the module does not accept
+/// are formatted all the same, and answered with `invalid_param`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `sys get ver`, answered with the firmware version.
+    SysGetVer,
+    /// `sys reset`, answered with the firmware version after the reset.
+    SysReset,
+    /// `sys factoryRESET`, answered with the firmware version after the reset.
+    SysFactoryReset,
+    /// `sys set nvm <address> <value>`
+    SysSetNvm(NvmAddress, u8),
+    /// `sys get nvm <address>`, answered with the value in hexadecimal.
+    SysGetNvm(NvmAddress),
+    /// `sys sleep <ms>`, answered with `ok` only once the module wakes up.
+    SysSleep(u32),
+    /// `sys get vdd`, answered with the supply voltage in millivolts.
+    SysGetVdd,
+    /// `sys get hweui`, answered with the preprogrammed EUI-64 in hexadecimal.
+    SysGetHwEui,
+    /// `sys set pinmode <pin> digout|digin|ana`
+    SysSetPinMode(Pin, PinMode),
+    /// `sys set pindig <pin> 0|1`
+    SysSetPinDigital(Pin, bool),
+    /// `sys get pindig <pin>`, answered with `0` or `1`.
+    SysGetPinDigital(Pin),
+    /// `sys get pinana <pin>`, answered with a 10-bit value in decimal.
+    SysGetPinAnalog(Pin),
+    /// `mac pause`, answered with the number of milliseconds the MAC may remain paused.
+    MacPause,
+    /// `mac resume`
+    MacResume,
+    /// `mac set <parameter> <value>`
+    MacSet(MacSetting),
+    /// `mac get <parameter>`
+    MacGet(MacParameter),
+    /// `mac join otaa|abp`, followed by an `accepted` or `denied` event.
+    MacJoin(JoinMode),
+    /// `mac tx cnf|uncnf <port> <data>`, followed by a `mac_tx_ok`, `mac_rx`, or
+    /// `mac_err` event.
+    MacTx {
+        /// Whether the uplink is sent as a confirmed message.
+        confirmed: bool,
+        /// The LoRaWAN port to send the uplink to.
+        port: u8,
+        /// The application payload.
+        payload: Vec<u8>,
+    },
+    /// `radio set <parameter> <value>`
+    RadioSet(RadioSetting),
+    /// `radio get <parameter>`
+    RadioGet(RadioParameter),
+    /// `radio rx <timeout>`, followed by a `radio_rx` or `radio_err` event.
+    RadioRx(u16),
+    /// `radio rxstop`
+    RadioRxStop,
+    /// `radio tx <data>`, followed by a `radio_tx_ok` or `radio_err` event.
+    RadioTx(Vec<u8>),
+}
+
+impl Command {
+    /// Returns whether the module may change radio settings in carrying out the command:
+    /// resets restore their defaults, and the MAC retunes the radio for every frame it
+    /// sends or receives once it joins, transmits, or resumes.
+    pub(crate) fn changes_radio(&self) -> bool {
+        matches!(
+            self,
+            Command::SysReset
+                | Command::SysFactoryReset
+                | Command::MacResume
+                | Command::MacJoin(_)
+                | Command::MacTx { .. }
+        )
+    }
+
+    /// Returns the command line as it is written to the module, including the CRLF.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut line = self.to_string().into_bytes();
+        line.extend_from_slice(b"\x0D\x0A");
+        line
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::SysGetVer => f.write_str("sys get ver"),
+            Command::SysReset => f.write_str("sys reset"),
+            Command::SysFactoryReset => f.write_str("sys factoryRESET"),
+            Command::SysSetNvm(address, value) => {
+                write!(f, "sys set nvm {:x} {:x}", address.inner(), value)
+            }
+            Command::SysGetNvm(address) => write!(f, "sys get nvm {:x}", address.inner()),
+            Command::SysSleep(ms) => write!(f, "sys sleep {}", ms),
+            Command::SysGetVdd => f.write_str("sys get vdd"),
+            Command::SysGetHwEui => f.write_str("sys get hweui"),
+            Command::SysSetPinMode(pin, mode) => {
+                write!(f, "sys set pinmode {} {}", pin.token(), mode.token())
+            }
+            Command::SysSetPinDigital(pin, high) => {
+                write!(f, "sys set pindig {} {}", pin.token(), *high as u8)
+            }
+            Command::SysGetPinDigital(pin) => write!(f, "sys get pindig {}", pin.token()),
+            Command::SysGetPinAnalog(pin) => write!(f, "sys get pinana {}", pin.token()),
+            Command::MacPause => f.write_str("mac pause"),
+            Command::MacResume => f.write_str("mac resume"),
+            Command::MacSet(setting) => write!(f, "mac set {}", setting),
+            Command::MacGet(parameter) => write!(f, "mac get {}", parameter),
+            Command::MacJoin(JoinMode::Otaa) => f.write_str("mac join otaa"),
+            Command::MacJoin(JoinMode::Abp) => f.write_str("mac join abp"),
+            Command::MacTx {
+                confirmed,
+                port,
+                payload,
+            } => write!(
+                f,
+                "mac tx {} {} {}",
+                if *confirmed { "cnf" } else { "uncnf" },
+                port,
+                hex::encode_upper(payload)
+            ),
+            Command::RadioSet(setting) => write!(f, "radio set {}", setting),
+            Command::RadioGet(parameter) => write!(f, "radio get {}", parameter),
+            Command::RadioRx(timeout) => write!(f, "radio rx {}", timeout),
+            Command::RadioRxStop => f.write_str("radio rxstop"),
+            Command::RadioTx(payload) => write!(f, "radio tx {}", hex::encode(payload)),
+        }
+    }
+}
+
+/// A LoRaWAN MAC parameter and the value to set it to, for `mac set`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacSetting {
+    /// `deveui`
+    DeviceEui(Eui64),
+    /// `appeui`
+    AppEui(Eui64),
+    /// `appkey`
+    AppKey(AesKey),
+    /// `devaddr`
+    DeviceAddress(DevAddr),
+    /// `nwkskey`
+    NetworkSessionKey(AesKey),
+    /// `appskey`
+    AppSessionKey(AesKey),
+    /// `ch freq`: a channel's frequency in Hz.
+    ChannelFrequency(u8, u32),
+    /// `ch dcycle`: a channel's duty cycle, which is `100 / (value + 1)` percent.
+    ChannelDutyCycle(u8, u16),
+    /// `ch drrange`: the lowest and highest data rate index used on a channel.
+    ChannelDataRateRange(u8, u8, u8),
+    /// `ch status`: whether a channel is used.
+    ChannelEnabled(u8, bool),
+}
+
+impl fmt::Display for MacSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacSetting::DeviceEui(eui) => write!(f, "deveui {}", eui),
+            MacSetting::AppEui(eui) => write!(f, "appeui {}", eui),
+            MacSetting::AppKey(key) => write!(f, "appkey {}", hex::encode_upper(key.bytes())),
+            MacSetting::DeviceAddress(address) => write!(f, "devaddr {}", address),
+            MacSetting::NetworkSessionKey(key) => {
+                write!(f, "nwkskey {}", hex::encode_upper(key.bytes()))
+            }
+            MacSetting::AppSessionKey(key) => {
+                write!(f, "appskey {}", hex::encode_upper(key.bytes()))
+            }
+            MacSetting::ChannelFrequency(channel, hz) => write!(f, "ch freq {} {}", channel, hz),
+            MacSetting::ChannelDutyCycle(channel, value) => {
+                write!(f, "ch dcycle {} {}", channel, value)
+            }
+            MacSetting::ChannelDataRateRange(channel, min, max) => {
+                write!(f, "ch drrange {} {} {}", channel, min, max)
+            }
+            MacSetting::ChannelEnabled(channel, enabled) => {
+                write!(f, "ch status {} {}", channel, on_off(*enabled))
+            }
+        }
+    }
+}
+
+/// A LoRaWAN MAC parameter which can be queried with `mac get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MacParameter {
+    /// `deveui`
+    DeviceEui,
+    /// `appeui`
+    AppEui,
+    /// `devaddr`
+    DeviceAddress,
+    /// `ch freq` of the given channel
+    ChannelFrequency(u8),
+    /// `ch dcycle` of the given channel
+    ChannelDutyCycle(u8),
+    /// `ch drrange` of the given channel
+    ChannelDataRateRange(u8),
+    /// `ch status` of the given channel
+    ChannelEnabled(u8),
+}
+
+impl fmt::Display for MacParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacParameter::DeviceEui => f.write_str("deveui"),
+            MacParameter::AppEui => f.write_str("appeui"),
+            MacParameter::DeviceAddress => f.write_str("devaddr"),
+            MacParameter::ChannelFrequency(channel) => write!(f, "ch freq {}", channel),
+            MacParameter::ChannelDutyCycle(channel) => write!(f, "ch dcycle {}", channel),
+            MacParameter::ChannelDataRateRange(channel) => write!(f, "ch drrange {}", channel),
+            MacParameter::ChannelEnabled(channel) => write!(f, "ch status {}", channel),
+        }
+    }
+}
+
+/// A radio parameter and the value to set it to, for `radio set`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RadioSetting {
+    /// `mod`. Both `Fsk` and `Gfsk` are written as `fsk`; the Gaussian filter is set
+    /// separately with `GaussianBt`.
+    Modulation(ModulationMode),
+    /// `bt`
+    GaussianBt(GaussianBt),
+    /// `sf`
+    SpreadingFactor(SpreadingFactor),
+    /// `bw`
+    Bandwidth(Bandwidth),
+    /// `cr`
+    CodingRate(CodingRate),
+    /// `freq`, in Hz.
+    Frequency(u32),
+    /// `pwr`, in dBm.
+    Power(i8),
+    /// `prlen`, in symbols.
+    PreambleLength(u16),
+    /// `crc`
+    Crc(bool),
+    /// `iqi`
+    IqInversion(bool),
+    /// `sync`, as the single byte used in LoRa mode.
+    SyncWord(u8),
+    /// `bitrate`, in bits per second.
+    FskBitrate(u32),
+    /// `fdev`, in Hz.
+    FrequencyDeviation(u32),
+    /// `rxbw`
+    RxBandwidth(FskBandwidth),
+    /// `afcbw`
+    AfcBandwidth(FskBandwidth),
+    /// `sync`, as the up to 8 bytes used in FSK mode.
+    FskSyncWord(Vec<u8>),
+    /// `wdt`, in milliseconds.
+    WatchdogTimeout(u32),
+}
+
+impl fmt::Display for RadioSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RadioSetting::Modulation(ModulationMode::LoRa) => f.write_str("mod lora"),
+            RadioSetting::Modulation(_) => f.write_str("mod fsk"),
+            RadioSetting::GaussianBt(bt) => write!(f, "bt {}", bt.token()),
+            RadioSetting::SpreadingFactor(sf) => write!(f, "sf {}", sf.token()),
+            RadioSetting::Bandwidth(bw) => write!(f, "bw {}", bw.token()),
+            RadioSetting::CodingRate(cr) => write!(f, "cr {}", cr.token()),
+            RadioSetting::Frequency(hz) => write!(f, "freq {}", hz),
+            RadioSetting::Power(dbm) => write!(f, "pwr {}", dbm),
+            RadioSetting::PreambleLength(symbols) => write!(f, "prlen {}", symbols),
+            RadioSetting::Crc(enabled) => write!(f, "crc {}", on_off(*enabled)),
+            RadioSetting::IqInversion(enabled) => write!(f, "iqi {}", on_off(*enabled)),
+            RadioSetting::SyncWord(sync) => write!(f, "sync {:02x}", sync),
+            RadioSetting::FskBitrate(bps) => write!(f, "bitrate {}", bps),
+            RadioSetting::FrequencyDeviation(hz) => write!(f, "fdev {}", hz),
+            RadioSetting::RxBandwidth(bw) => write!(f, "rxbw {}", bw.token()),
+            RadioSetting::AfcBandwidth(bw) => write!(f, "afcbw {}", bw.token()),
+            RadioSetting::FskSyncWord(sync) => write!(f, "sync {}", hex::encode(sync)),
+            RadioSetting::WatchdogTimeout(ms) => write!(f, "wdt {}", ms),
+        }
+    }
+}
+
+/// A radio parameter which can be queried with `radio get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RadioParameter {
+    /// `mod`, answered with `lora` or `fsk`.
+    Modulation,
+    /// `bt`
+    GaussianBt,
+    /// `sf`
+    SpreadingFactor,
+    /// `bw`
+    Bandwidth,
+    /// `cr`
+    CodingRate,
+    /// `freq`
+    Frequency,
+    /// `snr`, of the last packet received.
+    Snr,
+    /// `pktrssi`, of the last packet received. Added in firmware version 1.0.5.
+    PacketRssi,
+    /// `pwr`
+    Power,
+    /// `prlen`
+    PreambleLength,
+    /// `crc`
+    Crc,
+    /// `iqi`
+    IqInversion,
+    /// `sync`, in either LoRa or FSK mode.
+    SyncWord,
+    /// `bitrate`
+    FskBitrate,
+    /// `fdev`
+    FrequencyDeviation,
+    /// `rxbw`
+    RxBandwidth,
+    /// `afcbw`
+    AfcBandwidth,
+    /// `wdt`
+    WatchdogTimeout,
+}
+
+impl fmt::Display for RadioParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RadioParameter::Modulation => "mod",
+            RadioParameter::GaussianBt => "bt",
+            RadioParameter::SpreadingFactor => "sf",
+            RadioParameter::Bandwidth => "bw",
+            RadioParameter::CodingRate => "cr",
+            RadioParameter::Frequency => "freq",
+            RadioParameter::Snr => "snr",
+            RadioParameter::PacketRssi => "pktrssi",
+            RadioParameter::Power => "pwr",
+            RadioParameter::PreambleLength => "prlen",
+            RadioParameter::Crc => "crc",
+            RadioParameter::IqInversion => "iqi",
+            RadioParameter::SyncWord => "sync",
+            RadioParameter::FskBitrate => "bitrate",
+            RadioParameter::FrequencyDeviation => "fdev",
+            RadioParameter::RxBandwidth => "rxbw",
+            RadioParameter::AfcBandwidth => "afcbw",
+            RadioParameter::WatchdogTimeout => "wdt",
+        })
+    }
+}
+
+/// The line with which the module immediately answers a command.
+///
+/// The status replies documented in the command reference have their own variants;
+/// anything else, like the value answering a `get` command, is a `Value`. The `Display`
+/// implementation writes the line as the module sends it, without the CRLF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// `ok`
+    Ok,
+    /// `invalid_param`
+    InvalidParam,
+    /// `busy`
+    Busy,
+    /// `keys_not_init`
+    KeysNotInit,
+    /// `no_free_ch`
+    NoFreeChannel,
+    /// `silent`
+    Silent,
+    /// `mac_paused`
+    MacPaused,
+    /// `not_joined`
+    NotJoined,
+    /// `frame_counter_err_rejoin_needed`
+    FrameCounterErrRejoinNeeded,
+    /// `invalid_data_len`
+    InvalidDataLen,
+    /// Any other line, like a version string or the value of a parameter.
+    Value(String),
+}
+
+/// Every status `Response`, with the way the module writes it.
+const STATUS_RESPONSES: &[(Response, &str)] = &[
+    (Response::Ok, "ok"),
+    (Response::InvalidParam, "invalid_param"),
+    (Response::Busy, "busy"),
+    (Response::KeysNotInit, "keys_not_init"),
+    (Response::NoFreeChannel, "no_free_ch"),
+    (Response::Silent, "silent"),
+    (Response::MacPaused, "mac_paused"),
+    (Response::NotJoined, "not_joined"),
+    (
+        Response::FrameCounterErrRejoinNeeded,
+        "frame_counter_err_rejoin_needed",
+    ),
+    (Response::InvalidDataLen, "invalid_data_len"),
+];
+
+impl Response {
+    /// Parses a line sent by the module, without its CRLF. Leading and trailing
+    /// whitespace is ignored.
+    pub fn parse(line: &[u8]) -> Response {
+        let line = bytes_to_string(line);
+        let line = line.trim();
+        STATUS_RESPONSES
+            .iter()
+            .find(|(_, token)| *token == line)
+            .map(|(response, _)| response.clone())
+            .unwrap_or_else(|| Response::Value(line.to_string()))
+    }
+
+    /// Converts a response other than the one expected into the corresponding error.
+    pub(crate) fn into_error(self, expected: &str) -> Error {
+        match self {
+            Response::InvalidParam => Error::InvalidParam,
+            Response::Busy => Error::TransceiverBusy,
+            Response::KeysNotInit => Error::KeysNotInitialized,
+            Response::NoFreeChannel => Error::NoFreeChannel,
+            Response::Silent => Error::Silent,
+            Response::MacPaused => Error::MacPaused,
+            Response::NotJoined => Error::NotJoined,
+            Response::FrameCounterErrRejoinNeeded => Error::FrameCounterRejoinNeeded,
+            Response::InvalidDataLen => Error::InvalidDataLength,
+            Response::Ok | Response::Value(_) => Error::bad_response(expected, self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Value(value) => f.write_str(value),
+            status => {
+                let (_, token) = STATUS_RESPONSES
+                    .iter()
+                    .find(|(response, _)| response == status)
+                    .expect("every status Response is listed in STATUS_RESPONSES");
+                f.write_str(token)
+            }
+        }
+    }
+}
+
+/// A line the module sends on its own, after answering a command, to report the outcome
+/// of that command.
+///
+/// The `Display` implementation writes the line as the module sends it, without the CRLF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// `radio_rx <data>`: a packet was received.
+    RadioRx(Vec<u8>),
+    /// `radio_err`: reception timed out, or transmission was interrupted by the radio
+    /// watchdog.
+    RadioErr,
+    /// `radio_tx_ok`: a transmission finished.
+    RadioTxOk,
+    /// `accepted`: the network accepted a join.
+    Accepted,
+    /// `denied`: the network denied a join, or did not answer it.
+    Denied,
+    /// `mac_tx_ok`: an uplink was sent, and no downlink was received.
+    MacTxOk,
+    /// `mac_rx <port> <data>`: an uplink was sent, and a downlink was received.
+    MacRx(Downlink),
+    /// `mac_err`: an uplink failed, or a confirmed uplink was not acknowledged.
+    MacErr,
+    /// `invalid_data_len`: an uplink was too long for the data rate it was retried at.
+    InvalidDataLen,
+}
+
+impl Event {
+    /// Parses a line sent by the module, without its CRLF, returning `None` if it is not
+    /// an event or is malformed.
+    ///
+    /// Words may be separated by any amount of whitespace, and the payload of `radio_rx`
+    /// may be in either case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rn2903::protocol::Event;
+    ///
+    /// for line in &[&b"radio_rx CAFE"[..], b"radio_rx  CAFE", b"radio_rx\tcafe "] {
+    ///     assert_eq!(Event::parse(line), Some(Event::RadioRx(vec![0xCA, 0xFE])));
+    /// }
+    /// assert_eq!(Event::parse(b"radio_rx"), None);
+    /// assert_eq!(Event::parse(b"radio_rx CAF"), None);
+    /// assert_eq!(Event::parse(b"radio_err extra"), None);
+    /// ```
+    pub fn parse(line: &[u8]) -> Option<Event> {
+        let line = bytes_to_string(line);
+        let mut words = line.split_whitespace();
+        let event = match words.next()? {
+            "radio_err" => Event::RadioErr,
+            "radio_tx_ok" => Event::RadioTxOk,
+            "accepted" => Event::Accepted,
+            "denied" => Event::Denied,
+            "mac_tx_ok" => Event::MacTxOk,
+            "mac_err" => Event::MacErr,
+            "invalid_data_len" => Event::InvalidDataLen,
+            "mac_rx" => return Downlink::parse(&line).map(Event::MacRx),
+            "radio_rx" => Event::RadioRx(hex::decode(words.next()?).ok()?),
+            _ => return None,
+        };
+        match words.next() {
+            Some(_) => None,
+            None => Some(event),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // The firmware separates the payload with two spaces.
+            Event::RadioRx(payload) => write!(f, "radio_rx  {}", hex::encode_upper(payload)),
+            Event::RadioErr => f.write_str("radio_err"),
+            Event::RadioTxOk => f.write_str("radio_tx_ok"),
+            Event::Accepted => f.write_str("accepted"),
+            Event::Denied => f.write_str("denied"),
+            Event::MacTxOk => f.write_str("mac_tx_ok"),
+            Event::MacRx(downlink) if downlink.payload.is_empty() => {
+                write!(f, "mac_rx {}", downlink.port)
+            }
+            Event::MacRx(downlink) => write!(
+                f,
+                "mac_rx {} {}",
+                downlink.port,
+                hex::encode_upper(&downlink.payload)
+            ),
+            Event::MacErr => f.write_str("mac_err"),
+            Event::InvalidDataLen => f.write_str("invalid_data_len"),
+        }
+    }
+}
+
+/// Interprets the line the module answers a command with.
+type Interpret<V> = Box<dyn FnOnce(&[u8]) -> Result<V> + Send>;
+
+/// A command and the way to interpret the single line the module answers it with, shared
+/// by the front-ends.
+///
+/// Every operation which is a single command and a single reply is built by the function
+/// below of the same name as the `Rn2903` method. Operations which are answered twice,
+/// like `radio tx`, are described by a `Request` for the first reply, a function
+/// interpreting the event which follows, and the time to wait for it on top of the
+/// response timeout. The `operation` module combines these into the operations which
+/// take several exchanges.
+pub(crate) struct Request<V> {
+    command: Command,
+    interpret: Interpret<V>,
+}
+
+impl<V> Request<V> {
+    /// Creates a request for the given command, answered with a line which is interpreted
+    /// by the given function.
+    fn new<F>(command: Command, interpret: F) -> Self
+    where
+        F: FnOnce(&[u8]) -> Result<V> + Send + 'static,
+    {
+        Request {
+            command,
+            interpret: Box::new(interpret),
+        }
+    }
+
+    /// Creates a request for a command answered with a single value, like most `get`
+    /// commands. Replies the given function rejects are reported as errors.
+    fn value<F>(command: Command, expected: &'static str, parse: F) -> Self
+    where
+        F: FnOnce(&str) -> Option<V> + Send + 'static,
+    {
+        Request::new(command, move |response| match Response::parse(response) {
+            Response::Value(value) => match parse(&value) {
+                Some(v) => Ok(v),
+                None => Err(Error::bad_response(expected, value)),
+            },
+            other => Err(other.into_error(expected)),
+        })
+    }
+
+    /// Returns the command to send.
+    pub(crate) fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Interprets the module's reply to the command.
+    pub(crate) fn interpret(self, response: &[u8]) -> Result<V> {
+        (self.interpret)(response)
+    }
+}
+
+impl Request<()> {
+    /// Creates a request for a command answered with `ok` on success, like most `set`
+    /// commands, mapping the documented failure replies to errors.
+    fn ok(command: Command) -> Self {
+        Request::new(command, |response| match Response::parse(response) {
+            Response::Ok => Ok(()),
+            other => Err(other.into_error("ok")),
+        })
+    }
+}
+
+/// Checks the reply to `sys get ver` names a module this crate can drive, returning its
+/// parsed version.
+pub(crate) fn check_version(version: String) -> Result<FirmwareVersion> {
+    // RN2483 and RN2903 are the same, just EU and US chip, but talk the same
+    FirmwareVersion::parse(&version).ok_or(Error::WrongDevice(version))
+}
+
+pub(crate) fn system_version() -> Request<String> {
+    Request::new(Command::SysGetVer, |response| Ok(bytes_to_string(response)))
+}
+
+pub(crate) fn system_version_bytes() -> Request<Vec<u8>> {
+    Request::new(Command::SysGetVer, |response| Ok(response.to_vec()))
+}
+
+pub(crate) fn system_module_reset() -> Request<Vec<u8>> {
+    Request::new(Command::SysReset, |response| Ok(response.to_vec()))
+}
+
+pub(crate) fn system_factory_reset() -> Request<Vec<u8>> {
+    Request::new(Command::SysFactoryReset, |response| Ok(response.to_vec()))
+}
+
+pub(crate) fn system_set_nvm(address: NvmAddress, value: u8) -> Request<()> {
+    Request::ok(Command::SysSetNvm(address, value))
+}
+
+pub(crate) fn system_get_nvm(address: NvmAddress) -> Request<u8> {
+    Request::value(Command::SysGetNvm(address), "<integer>", |v| {
+        u8::from_str_radix(v, 16).ok()
+    })
+}
+
+pub(crate) fn system_vdd() -> Request<u16> {
+    Request::value(Command::SysGetVdd, "<integer>", |v| v.parse().ok())
+}
+
+pub(crate) fn system_hweui() -> Request<Eui64> {
+    Request::value(Command::SysGetHwEui, "<EUI-64>", Eui64::from_hex)
+}
+
+/// Puts the module to sleep. There is no `Request`, as the reply only arrives when the
+/// module wakes up, and is interpreted by `system_sleep_result()`.
+pub(crate) fn system_sleep(duration: Duration) -> Result<Command> {
+    let ms = duration.as_millis();
+    if !SLEEP_RANGE.contains(&ms) {
+        return Err(Error::out_of_range("sleep duration in ms", ms));
+    }
+    Ok(Command::SysSleep(ms as u32))
+}
+
+/// Interprets the reply to `sys sleep`, sent when the module wakes up.
+pub(crate) fn system_sleep_result(line: &[u8]) -> Result<()> {
+    match Response::parse(line) {
+        Response::Ok => Ok(()),
+        other => Err(other.into_error("ok")),
+    }
+}
+
+/// The byte sent after a break, from which the module measures the baud rate.
+pub(crate) const AUTOBAUD_BYTE: u8 = 0x55;
+
+/// How long the port must stay quiet, around the break which wakes the module, before
+/// everything it might still deliver is taken to have been received.
+pub(crate) const WAKE_QUIET_TIME: Duration = Duration::from_millis(20);
+
+/// Checks the pin can be read as an analog input.
+fn check_analog(pin: Pin) -> Result<()> {
+    if pin.supports_analog() {
+        Ok(())
+    } else {
+        Err(Error::out_of_range("analog pin", pin.token()))
+    }
+}
+
+pub(crate) fn system_set_pin_mode(pin: Pin, mode: PinMode) -> Result<Request<()>> {
+    if mode == PinMode::Analog {
+        check_analog(pin)?;
+    }
+    Ok(Request::ok(Command::SysSetPinMode(pin, mode)))
+}
+
+pub(crate) fn system_set_pin_digital(pin: Pin, high: bool) -> Request<()> {
+    Request::ok(Command::SysSetPinDigital(pin, high))
+}
+
+pub(crate) fn system_get_pin_digital(pin: Pin) -> Request<bool> {
+    Request::value(Command::SysGetPinDigital(pin), "0 | 1", |v| match v {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    })
+}
+
+pub(crate) fn system_get_pin_analog(pin: Pin) -> Result<Request<u16>> {
+    check_analog(pin)?;
+    Ok(Request::value(
+        Command::SysGetPinAnalog(pin),
+        "0 - 1023",
+        |v| v.parse().ok().filter(|&value| value <= MAX_ANALOG_VALUE),
+    ))
+}
+
+/// Sets a radio parameter, expecting `ok`.
+fn radio_set(setting: RadioSetting) -> Request<()> {
+    Request::ok(Command::RadioSet(setting))
+}
+
+/// Queries a radio parameter, parsing the value with the given function.
+fn radio_get<V, F>(parameter: RadioParameter, expected: &'static str, parse: F) -> Request<V>
+where
+    F: FnOnce(&str) -> Option<V> + Send + 'static,
+{
+    Request::value(Command::RadioGet(parameter), expected, parse)
+}
+
+/// Sets the modulation, without the Gaussian filter used in FSK mode.
+pub(crate) fn radio_set_modulation(mode: ModulationMode) -> Request<()> {
+    radio_set(RadioSetting::Modulation(mode))
+}
+
+/// Queries the modulation, reporting `true` for FSK and `false` for LoRa.
+pub(crate) fn radio_get_modulation() -> Request<bool> {
+    radio_get(RadioParameter::Modulation, "lora | fsk", |v| match v {
+        "lora" => Some(false),
+        "fsk" => Some(true),
+        _ => None,
+    })
+}
+
+pub(crate) fn radio_set_gaussian_bt(bt: GaussianBt) -> Request<()> {
+    radio_set(RadioSetting::GaussianBt(bt))
+}
+
+pub(crate) fn radio_get_gaussian_bt() -> Request<GaussianBt> {
+    radio_get(
+        RadioParameter::GaussianBt,
+        "none | 1.0 | 0.5 | 0.3",
+        GaussianBt::from_token,
+    )
+}
+
+/// Combines the replies to `radio get mod` and, in FSK mode, `radio get bt`.
+pub(crate) fn modulation_mode(fsk: bool, bt: GaussianBt) -> ModulationMode {
+    match (fsk, bt) {
+        (false, _) => ModulationMode::LoRa,
+        (true, GaussianBt::None) => ModulationMode::Fsk,
+        (true, bt) => ModulationMode::Gfsk(bt),
+    }
+}
+
+pub(crate) fn radio_set_spreading_factor(sf: SpreadingFactor) -> Request<()> {
+    radio_set(RadioSetting::SpreadingFactor(sf))
+}
+
+pub(crate) fn radio_get_spreading_factor() -> Request<SpreadingFactor> {
+    radio_get(
+        RadioParameter::SpreadingFactor,
+        "sf7 - sf12",
+        SpreadingFactor::from_token,
+    )
+}
+
+pub(crate) fn radio_set_bandwidth(bw: Bandwidth) -> Request<()> {
+    radio_set(RadioSetting::Bandwidth(bw))
+}
+
+pub(crate) fn radio_get_bandwidth() -> Request<Bandwidth> {
+    radio_get(
+        RadioParameter::Bandwidth,
+        "125 | 250 | 500",
+        Bandwidth::from_token,
+    )
+}
+
+pub(crate) fn radio_set_coding_rate(cr: CodingRate) -> Request<()> {
+    radio_set(RadioSetting::CodingRate(cr))
+}
+
+pub(crate) fn radio_get_coding_rate() -> Request<CodingRate> {
+    radio_get(
+        RadioParameter::CodingRate,
+        "4/5 - 4/8",
+        CodingRate::from_token,
+    )
+}
+
+pub(crate) fn radio_set_frequency(model: Option<Model>, hz: u32) -> Result<Request<()>> {
+    region::check_frequency(model, "radio frequency", hz)?;
+    Ok(radio_set(RadioSetting::Frequency(hz)))
+}
+
+pub(crate) fn radio_get_frequency() -> Request<u32> {
+    radio_get(RadioParameter::Frequency, "<integer>", |v| v.parse().ok())
+}
+
+pub(crate) fn radio_get_snr() -> Request<i8> {
+    radio_get(RadioParameter::Snr, "<integer>", |v| v.parse().ok())
+}
+
+/// Queries the RSSI of the last packet, which firmware before 1.0.5 rejects.
+pub(crate) fn radio_get_packet_rssi() -> Request<Option<i16>> {
+    Request::new(
+        Command::RadioGet(RadioParameter::PacketRssi),
+        |response| match Response::parse(response) {
+            Response::Value(value) => match value.parse() {
+                Ok(rssi) => Ok(Some(rssi)),
+                Err(_) => Err(Error::bad_response("<integer>", value)),
+            },
+            Response::InvalidParam => Ok(None),
+            other => Err(other.into_error("<integer>")),
+        },
+    )
+}
+
+pub(crate) fn radio_set_power(model: Option<Model>, dbm: i8) -> Result<Request<()>> {
+    region::check_power(model, dbm)?;
+    Ok(radio_set(RadioSetting::Power(dbm)))
+}
+
+pub(crate) fn radio_get_power() -> Request<i8> {
+    radio_get(RadioParameter::Power, "<integer>", |v| v.parse().ok())
+}
+
+pub(crate) fn radio_set_preamble_length(symbols: u16) -> Request<()> {
+    radio_set(RadioSetting::PreambleLength(symbols))
+}
+
+pub(crate) fn radio_get_preamble_length() -> Request<u16> {
+    radio_get(RadioParameter::PreambleLength, "<integer>", |v| {
+        v.parse().ok()
+    })
+}
+
+pub(crate) fn radio_set_crc(enabled: bool) -> Request<()> {
+    radio_set(RadioSetting::Crc(enabled))
+}
+
+pub(crate) fn radio_get_crc() -> Request<bool> {
+    radio_get(RadioParameter::Crc, "on | off", parse_on_off)
+}
+
+pub(crate) fn radio_set_iq_inversion(enabled: bool) -> Request<()> {
+    radio_set(RadioSetting::IqInversion(enabled))
+}
+
+pub(crate) fn radio_get_iq_inversion() -> Request<bool> {
+    radio_get(RadioParameter::IqInversion, "on | off", parse_on_off)
+}
+
+pub(crate) fn radio_set_sync_word(sync: u8) -> Request<()> {
+    radio_set(RadioSetting::SyncWord(sync))
+}
+
+pub(crate) fn radio_get_sync_word() -> Request<u8> {
+    radio_get(RadioParameter::SyncWord, "<hex byte>", |v| {
+        u8::from_str_radix(v, 16).ok()
+    })
+}
+
+pub(crate) fn radio_set_fsk_bitrate(bps: u32) -> Result<Request<()>> {
+    if !BITRATE_RANGE.contains(&bps) {
+        return Err(Error::out_of_range("FSK bit rate", bps));
+    }
+    Ok(radio_set(RadioSetting::FskBitrate(bps)))
+}
+
+pub(crate) fn radio_get_fsk_bitrate() -> Request<u32> {
+    radio_get(RadioParameter::FskBitrate, "<integer>", |v| v.parse().ok())
+}
+
+pub(crate) fn radio_set_frequency_deviation(hz: u32) -> Result<Request<()>> {
+    if !FREQUENCY_DEVIATION_RANGE.contains(&hz) {
+        return Err(Error::out_of_range("FSK frequency deviation", hz));
+    }
+    Ok(radio_set(RadioSetting::FrequencyDeviation(hz)))
+}
+
+pub(crate) fn radio_get_frequency_deviation() -> Request<u32> {
+    radio_get(RadioParameter::FrequencyDeviation, "<integer>", |v| {
+        v.parse().ok()
+    })
+}
+
+pub(crate) fn radio_set_rx_bandwidth(bw: FskBandwidth) -> Request<()> {
+    radio_set(RadioSetting::RxBandwidth(bw))
+}
+
+pub(crate) fn radio_get_rx_bandwidth() -> Request<FskBandwidth> {
+    radio_get(
+        RadioParameter::RxBandwidth,
+        "<bandwidth>",
+        FskBandwidth::from_token,
+    )
+}
+
+pub(crate) fn radio_set_afc_bandwidth(bw: FskBandwidth) -> Request<()> {
+    radio_set(RadioSetting::AfcBandwidth(bw))
+}
+
+pub(crate) fn radio_get_afc_bandwidth() -> Request<FskBandwidth> {
+    radio_get(
+        RadioParameter::AfcBandwidth,
+        "<bandwidth>",
+        FskBandwidth::from_token,
+    )
+}
+
+pub(crate) fn radio_set_fsk_sync_word(sync: &[u8]) -> Result<Request<()>> {
+    if sync.is_empty() || sync.len() > MAX_FSK_SYNC_WORD_LEN {
+        return Err(Error::out_of_range("FSK sync word length", sync.len()));
+    }
+    Ok(radio_set(RadioSetting::FskSyncWord(sync.to_vec())))
+}
+
+pub(crate) fn radio_get_fsk_sync_word() -> Request<Vec<u8>> {
+    radio_get(RadioParameter::SyncWord, "<hex bytes>", |v| {
+        hex::decode(v).ok()
+    })
+}
+
+pub(crate) fn radio_set_watchdog_timeout(ms: u32) -> Request<()> {
+    radio_set(RadioSetting::WatchdogTimeout(ms))
+}
+
+pub(crate) fn radio_get_watchdog_timeout() -> Request<u32> {
+    radio_get(RadioParameter::WatchdogTimeout, "<integer>", |v| {
+        v.parse().ok()
+    })
+}
+
+/// Opens the receiver; the outcome is reported by an event, interpreted by
+/// `radio_rx_result()`.
+pub(crate) fn radio_rx(timeout: u16) -> Request<()> {
+    Request::new(
+        Command::RadioRx(timeout),
+        |response| match Response::parse(response) {
+            Response::Ok => Ok(()),
+            Response::Busy => Err(Error::TransceiverBusy),
+            other => Err(other.into_error("ok")),
+        },
+    )
+}
+
+/// How long to wait for the outcome of `radio rx` or `radio tx`, beyond the response
+/// timeout, given the radio watchdog timeout in milliseconds, or `None` if the watchdog
+/// is disabled.
+///
+/// The watchdog ends any reception or transmission which outlasts it with `radio_err`,
+/// so the outcome is reported by the time it expires whatever the radio settings. The
+/// module starts the watchdog as it answers `ok`, so its report can be due the moment
+/// the host's wait runs out; `RADIO_WAIT_MARGIN` is allowed for that, and the response
+/// timeout for the report to cross the serial link.
+pub(crate) fn radio_wait(watchdog_timeout: u32) -> Option<Duration> {
+    match watchdog_timeout {
+        0 => None,
+        ms => Some(Duration::from_millis(ms.into()) + RADIO_WAIT_MARGIN),
+    }
+}
+
+/// Time allowed on top of how long the radio can take, for the module's clock and the
+/// host's to disagree.
+const RADIO_WAIT_MARGIN: Duration = Duration::from_millis(500);
+
+/// What the radio has been asked to do, for working out how long it can take.
+pub(crate) enum RadioActivity {
+    /// `radio rx` with the given timeout, in symbols for LoRa or milliseconds for FSK.
+    Receive(u16),
+    /// `radio tx` with a payload of the given length.
+    Transmit(usize),
+}
+
+/// The radio settings which determine how long a packet takes on air.
+pub(crate) enum AirSettings {
+    LoRa {
+        spreading_factor: SpreadingFactor,
+        bandwidth: Bandwidth,
+        preamble: u16,
+    },
+    Fsk {
+        bitrate: u32,
+        preamble: u16,
+    },
+}
+
+/// How long to wait for the outcome of `radio rx` or `radio tx` with the watchdog
+/// disabled, beyond the response timeout: the receive window and the longest packet
+/// which could be arriving as it closes, or the packet being sent. `radio rx 0` keeps
+/// the receiver open until a packet arrives, so has no limit.
+pub(crate) fn radio_activity_wait(
+    activity: &RadioActivity,
+    settings: &AirSettings,
+) -> Option<Duration> {
+    let (window, len) = match *activity {
+        RadioActivity::Receive(0) => return None,
+        RadioActivity::Receive(timeout) => {
+            let window = match *settings {
+                AirSettings::LoRa {
+                    spreading_factor,
+                    bandwidth,
+                    ..
+                } => lora_symbol_time(spreading_factor, bandwidth) * u32::from(timeout),
+                AirSettings::Fsk { .. } => Duration::from_millis(timeout.into()),
+            };
+            let len = match settings {
+                AirSettings::LoRa { .. } => MAX_LORA_PAYLOAD_LEN,
+                AirSettings::Fsk { .. } => MAX_FSK_PAYLOAD_LEN,
+            };
+            (window, len)
+        }
+        RadioActivity::Transmit(len) => (Duration::from_secs(0), len),
+    };
+    Some(window + airtime(settings, len) + RADIO_WAIT_MARGIN)
+}
+
+/// The duration of a LoRa symbol, `2^SF` chips at one chip per hertz of bandwidth.
+fn lora_symbol_time(sf: SpreadingFactor, bw: Bandwidth) -> Duration {
+    Duration::from_nanos((1_000_000_000 << sf.value()) / u64::from(bw.hz()))
+}
+
+/// The longest a packet with a payload of the given length can take on air.
+///
+/// The coding rate is not known, so LoRa packets are taken to use the slowest, 4/8,
+/// along with an explicit header, a CRC, and low data rate optimization at SF11 and SF12,
+/// as in Semtech's LoRa modem designer's guide. FSK packets are taken to carry the
+/// longest sync word, a length byte, and a CRC.
+fn airtime(settings: &AirSettings, len: usize) -> Duration {
+    match *settings {
+        AirSettings::LoRa {
+            spreading_factor,
+            bandwidth,
+            preamble,
+        } => {
+            let sf = i64::from(spreading_factor.value());
+            let optimized = if sf >= 11 { 1 } else { 0 };
+            let bits = 8 * len as i64 - 4 * sf + 28 + 16;
+            let bits_per_block = 4 * (sf - 2 * optimized);
+            let blocks = (bits.max(0) + bits_per_block - 1) / bits_per_block;
+            let payload_symbols = 8 + blocks * 8;
+            // The preamble is followed by 4.25 symbols of sync word and frame delimiter.
+            let quarter_symbols = 4 * (i64::from(preamble) + payload_symbols) + 17;
+            lora_symbol_time(spreading_factor, bandwidth) * quarter_symbols as u32 / 4
+        }
+        AirSettings::Fsk { bitrate, preamble } => {
+            let bytes = u64::from(preamble) + 8 + 1 + len as u64 + 2;
+            Duration::from_micros(bytes * 8 * 1_000_000 / u64::from(bitrate.max(1)))
+        }
+    }
+}
+
+/// Interprets the event which reports the outcome of `radio rx`, returning `Ok(None)` if
+/// no packet was received.
+pub(crate) fn radio_rx_result(line: &[u8]) -> Result<Option<Vec<u8>>> {
+    match Event::parse(line) {
+        Some(Event::RadioRx(payload)) => Ok(Some(payload)),
+        Some(Event::RadioErr) => Ok(None),
+        _ => Err(Error::bad_response(
+            "radio_err | radio_rx <bytes>",
+            bytes_to_string(line),
+        )),
+    }
+}
+
+pub(crate) fn radio_rx_stop() -> Request<()> {
+    Request::ok(Command::RadioRxStop)
+}
+
+/// How long to wait, after the module acknowledges `radio rxstop`, for the outcome of a
+/// reception it finished at the same moment.
+pub(crate) const RX_STOP_QUIET_TIME: Duration = Duration::from_millis(20);
+
+/// Starts transmitting the payload; the outcome is reported by an event, interpreted by
+/// `radio_tx_result()`.
+///
+/// Payloads longer than the module accepts in the given modulation mode are rejected. The
+/// spreading factor and bandwidth do not change the limit, so are not needed.
+pub(crate) fn radio_tx(payload: &[u8], modulation: ModulationMode) -> Result<Request<()>> {
+    let max_len = match modulation {
+        ModulationMode::LoRa => MAX_LORA_PAYLOAD_LEN,
+        ModulationMode::Fsk | ModulationMode::Gfsk(_) => MAX_FSK_PAYLOAD_LEN,
+    };
+    if payload.len() > max_len {
+        return Err(Error::out_of_range("radio payload length", payload.len()));
+    }
+    Ok(Request::new(
+        Command::RadioTx(payload.to_vec()),
+        |response| match Response::parse(response) {
+            Response::Ok => Ok(()),
+            other => Err(other.into_error("ok | busy")),
+        },
+    ))
+}
+
+/// Interprets the event which reports the outcome of `radio tx`.
+pub(crate) fn radio_tx_result(line: &[u8]) -> Result<()> {
+    match Event::parse(line) {
+        Some(Event::RadioTxOk) => Ok(()),
+        Some(Event::RadioErr) => Err(Error::TransmissionUnsuccessful),
+        _ => Err(Error::bad_response(
+            "radio_tx_ok | radio_err",
+            bytes_to_string(line),
+        )),
+    }
+}
+
+/// Pauses the MAC, reporting how many milliseconds it may remain paused.
+pub(crate) fn mac_pause() -> Request<u32> {
+    Request::new(Command::MacPause, |response| {
+        let val = bytes_to_string(response);
+        let ms: u32 = match val.trim().parse() {
+            Ok(v) => v,
+            Err(_) => return Err(Error::bad_response("<integer>", val)),
+        };
+        if ms == 0 {
+            Err(Error::CannotPause)
+        } else {
+            Ok(ms)
+        }
+    })
+}
+
+pub(crate) fn mac_resume() -> Request<()> {
+    Request::ok(Command::MacResume)
+}
+
+pub(crate) fn mac_set_device_eui(eui: Eui64) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::DeviceEui(eui)))
+}
+
+pub(crate) fn mac_get_device_eui() -> Request<Eui64> {
+    Request::value(
+        Command::MacGet(MacParameter::DeviceEui),
+        "<EUI-64>",
+        Eui64::from_hex,
+    )
+}
+
+pub(crate) fn mac_set_app_eui(eui: Eui64) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::AppEui(eui)))
+}
+
+pub(crate) fn mac_get_app_eui() -> Request<Eui64> {
+    Request::value(
+        Command::MacGet(MacParameter::AppEui),
+        "<EUI-64>",
+        Eui64::from_hex,
+    )
+}
+
+pub(crate) fn mac_set_app_key(key: AesKey) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::AppKey(key)))
+}
+
+pub(crate) fn mac_set_device_address(address: DevAddr) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::DeviceAddress(address)))
+}
+
+pub(crate) fn mac_get_device_address() -> Request<DevAddr> {
+    Request::value(
+        Command::MacGet(MacParameter::DeviceAddress),
+        "<DevAddr>",
+        |v| {
+            if v.len() == 8 {
+                u32::from_str_radix(v, 16).ok().map(DevAddr)
+            } else {
+                None
+            }
+        },
+    )
+}
+
+pub(crate) fn mac_set_network_session_key(key: AesKey) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::NetworkSessionKey(key)))
+}
+
+pub(crate) fn mac_set_app_session_key(key: AesKey) -> Request<()> {
+    Request::ok(Command::MacSet(MacSetting::AppSessionKey(key)))
+}
+
+pub(crate) fn mac_set_channel_frequency(
+    model: Option<Model>,
+    channel: u8,
+    hz: u32,
+) -> Result<Request<()>> {
+    region::check_channel(model, channel)?;
+    if model == Some(Model::Rn2903) || channel < FIRST_SETTABLE_CHANNEL {
+        return Err(Error::out_of_range(
+            "channel with a settable frequency",
+            channel,
+        ));
+    }
+    region::check_frequency(model, "channel frequency", hz)?;
+    Ok(Request::ok(Command::MacSet(MacSetting::ChannelFrequency(
+        channel, hz,
+    ))))
+}
+
+pub(crate) fn mac_get_channel_frequency(model: Option<Model>, channel: u8) -> Result<Request<u32>> {
+    region::check_channel(model, channel)?;
+    Ok(Request::value(
+        Command::MacGet(MacParameter::ChannelFrequency(channel)),
+        "<integer>",
+        |v| v.parse().ok(),
+    ))
+}
+
+pub(crate) fn mac_set_channel_duty_cycle(
+    model: Option<Model>,
+    channel: u8,
+    value: u16,
+) -> Result<Request<()>> {
+    region::check_channel(model, channel)?;
+    if model == Some(Model::Rn2903) {
+        return Err(Error::out_of_range("channel with a duty cycle", channel));
+    }
+    Ok(Request::ok(Command::MacSet(MacSetting::ChannelDutyCycle(
+        channel, value,
+    ))))
+}
+
+pub(crate) fn mac_get_channel_duty_cycle(
+    model: Option<Model>,
+    channel: u8,
+) -> Result<Request<u16>> {
+    region::check_channel(model, channel)?;
+    Ok(Request::value(
+        Command::MacGet(MacParameter::ChannelDutyCycle(channel)),
+        "<integer>",
+        |v| v.parse().ok(),
+    ))
+}
+
+/// Sets the data rates used on a channel; ranges which are empty or reach beyond the
+/// highest index the module knows are rejected.
+pub(crate) fn mac_set_channel_data_rate_range(
+    model: Option<Model>,
+    channel: u8,
+    min: u8,
+    max: u8,
+) -> Result<Request<()>> {
+    region::check_channel(model, channel)?;
+    if min > max || max > MAX_DATA_RATE {
+        return Err(Error::out_of_range(
+            "data rate range",
+            format!("{} to {}", min, max),
+        ));
+    }
+    Ok(Request::ok(Command::MacSet(
+        MacSetting::ChannelDataRateRange(channel, min, max),
+    )))
+}
+
+pub(crate) fn mac_get_channel_data_rate_range(
+    model: Option<Model>,
+    channel: u8,
+) -> Result<Request<(u8, u8)>> {
+    region::check_channel(model, channel)?;
+    Ok(Request::value(
+        Command::MacGet(MacParameter::ChannelDataRateRange(channel)),
+        "<integer> <integer>",
+        |v| {
+            let mut words = v.split_whitespace();
+            let min = words.next()?.parse().ok()?;
+            let max = words.next()?.parse().ok()?;
+            match words.next() {
+                None => Some((min, max)),
+                Some(_) => None,
+            }
+        },
+    ))
+}
+
+pub(crate) fn mac_set_channel_enabled(
+    model: Option<Model>,
+    channel: u8,
+    enabled: bool,
+) -> Result<Request<()>> {
+    region::check_channel(model, channel)?;
+    Ok(Request::ok(Command::MacSet(MacSetting::ChannelEnabled(
+        channel, enabled,
+    ))))
+}
+
+pub(crate) fn mac_get_channel_enabled(model: Option<Model>, channel: u8) -> Result<Request<bool>> {
+    region::check_channel(model, channel)?;
+    Ok(Request::value(
+        Command::MacGet(MacParameter::ChannelEnabled(channel)),
+        "on | off",
+        parse_on_off,
+    ))
+}
+
+/// Starts joining a network; the outcome is reported by an event, interpreted by
+/// `mac_join_result()`.
+pub(crate) fn mac_join(mode: JoinMode) -> Request<()> {
+    Request::new(Command::MacJoin(mode), |response| {
+        match Response::parse(response) {
+            Response::Ok => Ok(()),
+            other => {
+                Err(other
+                    .into_error("ok | keys_not_init | no_free_ch | silent | busy | mac_paused"))
+            }
+        }
+    })
+}
+
+/// How long to wait for the outcome of `mac join`, beyond the response timeout: the join
+/// request, the second join accept window, and the join accept.
+pub(crate) const MAC_JOIN_WAIT: Duration = Duration::from_secs(
+    MAX_LORAWAN_AIRTIME.as_secs() + JOIN_ACCEPT_DELAY2.as_secs() + MAX_LORAWAN_AIRTIME.as_secs(),
+);
+
+/// Interprets the event which reports the outcome of `mac join`.
+pub(crate) fn mac_join_result(line: &[u8]) -> Result<()> {
+    match Event::parse(line) {
+        Some(Event::Accepted) => Ok(()),
+        Some(Event::Denied) => Err(Error::JoinDenied),
+        _ => Err(Error::bad_response(
+            "accepted | denied",
+            bytes_to_string(line),
+        )),
+    }
+}
+
+/// Starts sending an uplink; the outcome is reported by an event, interpreted by
+/// `mac_tx_result()`.
+///
+/// Ports outside the application range are rejected.
+pub(crate) fn mac_tx(confirmed: bool, port: u8, payload: &[u8]) -> Result<Request<()>> {
+    if !APPLICATION_PORTS.contains(&port) {
+        return Err(Error::out_of_range("LoRaWAN port", port));
+    }
+    let command = Command::MacTx {
+        confirmed,
+        port,
+        payload: payload.to_vec(),
+    };
+    Ok(Request::new(command, |response| {
+        match Response::parse(response) {
+            Response::Ok => Ok(()),
+            other => {
+                Err(other.into_error("ok | not_joined | no_free_ch | silent | busy | mac_paused"))
+            }
+        }
+    }))
+}
+
+/// How long to wait for the outcome of `mac tx`, beyond the response timeout: the uplink,
+/// both receive windows, and a downlink for every attempt, along with the wait before
+/// each retransmission.
+pub(crate) fn mac_tx_wait(confirmed: bool) -> Duration {
+    let attempt = MAX_LORAWAN_AIRTIME + RECEIVE_DELAY2 + MAX_LORAWAN_AIRTIME;
+    if confirmed {
+        attempt * CONFIRMED_ATTEMPTS + ACK_TIMEOUT * (CONFIRMED_ATTEMPTS - 1)
+    } else {
+        attempt
+    }
+}
+
+/// Interprets the event which reports the outcome of `mac tx`.
+pub(crate) fn mac_tx_result(line: &[u8]) -> Result<Option<Downlink>> {
+    match Event::parse(line) {
+        Some(Event::MacTxOk) => Ok(None),
+        Some(Event::MacRx(downlink)) => Ok(Some(downlink)),
+        Some(Event::MacErr) => Err(Error::MacTransmissionFailed),
+        Some(Event::InvalidDataLen) => Err(Error::InvalidDataLength),
+        _ => Err(Error::bad_response(
+            "mac_tx_ok | mac_rx <port> <data> | mac_err",
+            bytes_to_string(line),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::LineBuffer;
+    use crate::Pin;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    /// Arbitrary bytes, or a line which starts like one the module sends and continues
+    /// with the characters its replies are made of, so parsing gets past the first word.
+    fn line() -> impl Strategy<Value = Vec<u8>> {
+        let prefixes: &[&[u8]] = &[
+            b"",
+            b"ok",
+            b"radio_rx ",
+            b"radio_rx  ",
+            b"radio_err",
+            b"mac_rx ",
+            b"mac_tx_ok",
+            b"accepted",
+            b"RN2903 1.0.5 ",
+            b"RN2483 1.0.1 ",
+        ];
+        let alphabet: &[u8] = b" \t\r\n.0123456789ABCDEFabcdef_xz\x00\xC3\xFF";
+        prop_oneof![
+            vec(any::<u8>(), 0..4096),
+            (
+                proptest::sample::select(prefixes),
+                vec(proptest::sample::select(alphabet), 0..64),
+            )
+                .prop_map(|(prefix, rest)| [prefix, &rest[..]].concat()),
+        ]
+    }
+
+    /// Passes the line to the interpreter of every request, ignoring the outcome.
+    fn interpret_all(line: &[u8]) {
+        macro_rules! interpret {
+            ($($request:expr),* $(,)?) => {
+                $(let _ = $request.interpret(line);)*
+            };
+        }
+        let model = Some(Model::Rn2903);
+        let address = NvmAddress::new(0x300);
+        let key = AesKey::from([0; 16]);
+        interpret!(
+            system_version(),
+            system_version_bytes(),
+            system_module_reset(),
+            system_factory_reset(),
+            system_set_nvm(address, 0),
+            system_get_nvm(address),
+            system_vdd(),
+            system_hweui(),
+            system_set_pin_mode(Pin::Gpio0, PinMode::Analog).unwrap(),
+            system_set_pin_digital(Pin::Gpio0, true),
+            system_get_pin_digital(Pin::Gpio0),
+            system_get_pin_analog(Pin::Gpio0).unwrap(),
+            radio_set_modulation(ModulationMode::LoRa),
+            radio_get_modulation(),
+            radio_set_gaussian_bt(GaussianBt::None),
+            radio_get_gaussian_bt(),
+            radio_set_spreading_factor(SpreadingFactor::Sf7),
+            radio_get_spreading_factor(),
+            radio_set_bandwidth(Bandwidth::Khz125),
+            radio_get_bandwidth(),
+            radio_set_coding_rate(CodingRate::Cr4_5),
+            radio_get_coding_rate(),
+            radio_set_frequency(model, 915_000_000).unwrap(),
+            radio_get_frequency(),
+            radio_get_snr(),
+            radio_get_packet_rssi(),
+            radio_set_power(model, 2).unwrap(),
+            radio_get_power(),
+            radio_set_preamble_length(8),
+            radio_get_preamble_length(),
+            radio_set_crc(true),
+            radio_get_crc(),
+            radio_set_iq_inversion(false),
+            radio_get_iq_inversion(),
+            radio_set_sync_word(0x12),
+            radio_get_sync_word(),
+            radio_set_fsk_bitrate(50_000).unwrap(),
+            radio_get_fsk_bitrate(),
+            radio_set_frequency_deviation(25_000).unwrap(),
+            radio_get_frequency_deviation(),
+            radio_set_rx_bandwidth(FskBandwidth::Khz2_6),
+            radio_get_rx_bandwidth(),
+            radio_set_afc_bandwidth(FskBandwidth::Khz2_6),
+            radio_get_afc_bandwidth(),
+            radio_set_fsk_sync_word(&[0xC1, 0x94, 0xC1]).unwrap(),
+            radio_get_fsk_sync_word(),
+            radio_set_watchdog_timeout(15_000),
+            radio_get_watchdog_timeout(),
+            radio_rx(0),
+            radio_rx_stop(),
+            radio_tx(&[0], ModulationMode::LoRa).unwrap(),
+            mac_pause(),
+            mac_resume(),
+            mac_set_device_eui(Eui64::from([0; 8])),
+            mac_get_device_eui(),
+            mac_set_app_eui(Eui64::from([0; 8])),
+            mac_get_app_eui(),
+            mac_set_app_key(key),
+            mac_set_device_address(DevAddr(0)),
+            mac_get_device_address(),
+            mac_set_network_session_key(key),
+            mac_set_app_session_key(key),
+            mac_set_channel_frequency(Some(Model::Rn2483), 3, 868_100_000).unwrap(),
+            mac_get_channel_frequency(model, 0).unwrap(),
+            mac_set_channel_duty_cycle(Some(Model::Rn2483), 3, 99).unwrap(),
+            mac_get_channel_duty_cycle(model, 0).unwrap(),
+            mac_set_channel_data_rate_range(model, 0, 0, 3).unwrap(),
+            mac_get_channel_data_rate_range(model, 0).unwrap(),
+            mac_set_channel_enabled(model, 0, true).unwrap(),
+            mac_get_channel_enabled(model, 0).unwrap(),
+            mac_join(JoinMode::Otaa),
+            mac_tx(false, 1, &[0]).unwrap(),
+        );
+    }
+
+    #[test]
+    fn lora_airtime_matches_the_designers_guide() {
+        let settings = AirSettings::LoRa {
+            spreading_factor: SpreadingFactor::Sf7,
+            bandwidth: Bandwidth::Khz125,
+            preamble: 8,
+        };
+        // 12.25 preamble symbols and 40 payload symbols of 1.024 ms.
+        assert_eq!(airtime(&settings, 10), Duration::from_micros(53_504));
+    }
+
+    #[test]
+    fn radio_waits_are_bounded_except_for_continuous_reception() {
+        let settings = AirSettings::Fsk {
+            bitrate: 50_000,
+            preamble: 5,
+        };
+        assert_eq!(
+            radio_activity_wait(&RadioActivity::Receive(0), &settings),
+            None
+        );
+        let wait = radio_activity_wait(&RadioActivity::Receive(100), &settings).unwrap();
+        assert!(wait > Duration::from_millis(100) + airtime(&settings, 64));
+        let wait = radio_activity_wait(&RadioActivity::Transmit(10), &settings).unwrap();
+        assert!(wait > airtime(&settings, 10));
+        assert!(radio_wait(1000).unwrap() > Duration::from_millis(1000));
+        assert_eq!(radio_wait(0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn request_interpreters_accept_any_line(line in line()) {
+            interpret_all(&line);
+        }
+
+        #[test]
+        fn event_interpreters_accept_any_line(line in line()) {
+            let _ = radio_rx_result(&line);
+            let _ = radio_tx_result(&line);
+            let _ = system_sleep_result(&line);
+            let _ = mac_join_result(&line);
+            let _ = mac_tx_result(&line);
+        }
+
+        #[test]
+        fn value_parsers_accept_any_text(line in line()) {
+            let text = bytes_to_string(&line);
+            let _ = Downlink::parse(&text);
+            let _ = FirmwareVersion::parse(&text);
+            let _ = check_version(text.clone());
+            let _ = Eui64::from_hex(&text);
+        }
+
+        #[test]
+        fn responses_accept_any_line(line in line()) {
+            let response = Response::parse(&line);
+            let _ = response.to_string();
+            let _ = response.into_error("ok");
+        }
+
+        #[test]
+        fn events_format_back_to_the_same_event(line in line()) {
+            if let Some(event) = Event::parse(&line) {
+                prop_assert_eq!(Event::parse(event.to_string().as_bytes()), Some(event));
+            }
+        }
+
+        #[test]
+        fn line_buffer_frames_any_stream(
+            bytes in line(),
+            splits in vec(any::<prop::sample::Index>(), 0..8),
+        ) {
+            let mut splits: Vec<usize> = splits.iter().map(|i| i.index(bytes.len() + 1)).collect();
+            splits.push(0);
+            splits.push(bytes.len());
+            splits.sort_unstable();
+
+            let mut buffer = LineBuffer::default();
+            let mut framed = Vec::new();
+            for chunk in splits.windows(2) {
+                buffer.extend(&bytes[chunk[0]..chunk[1]]);
+                while let Some(line) = buffer.next_line() {
+                    prop_assert!(!line.windows(2).any(|w| w == b"\r\n"));
+                    framed.extend_from_slice(&line);
+                    framed.extend_from_slice(b"\r\n");
+                }
+            }
+            // Terminating the stream hands back whatever was left over unchanged.
+            buffer.extend(b"\r\n");
+            framed.extend_from_slice(&buffer.next_line().expect("a line was just ended"));
+            prop_assert_eq!(framed, bytes);
+        }
+    }
+}
@@ -0,0 +1,748 @@
+//! Types and functions for driving the LoRa/FSK radio directly, with the MAC paused.
+
+use crate::operation;
+use crate::protocol;
+use crate::{Result, Rn2903, Transport};
+use core::time::Duration;
+use std::time::{Instant, SystemTime};
+
+/// Radio settings which have been set or queried through this crate, so that they need
+/// not be queried again. Forgotten whenever the module is reset, the MAC takes over the
+/// radio, or a raw command is sent.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RadioState {
+    pub(crate) modulation: Option<ModulationMode>,
+    pub(crate) frequency: Option<u32>,
+    pub(crate) spreading_factor: Option<SpreadingFactor>,
+    pub(crate) bandwidth: Option<Bandwidth>,
+    pub(crate) watchdog_timeout: Option<u32>,
+}
+
+/// The outcome of a successful radio transmission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transmission {
+    /// When the module accepted the payload and began transmitting.
+    pub started: Instant,
+    /// When the module reported that the transmission had finished.
+    pub finished: Instant,
+}
+
+impl Transmission {
+    /// Returns the time between the start and end of the transmission, which approximates
+    /// the time on air.
+    pub fn duration(&self) -> Duration {
+        self.finished - self.started
+    }
+}
+
+/// A packet received by the radio, along with information about how it was received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedPacket {
+    /// The payload of the packet.
+    pub payload: Vec<u8>,
+    /// The signal-to-noise ratio of the packet in dB, if it was received in LoRa mode.
+    pub snr: Option<i8>,
+    /// The received signal strength of the packet in dBm, if the firmware reports it.
+    pub rssi: Option<i16>,
+    /// The frequency, in Hz, the radio was tuned to.
+    pub frequency: u32,
+    /// The modulation mode the radio was in.
+    pub modulation: ModulationMode,
+    /// The spreading factor the radio was using, if it was in LoRa mode.
+    pub spreading_factor: Option<SpreadingFactor>,
+    /// The bandwidth the radio was using, if it was in LoRa mode.
+    pub bandwidth: Option<Bandwidth>,
+    /// When the host was notified of the packet.
+    pub received_at: SystemTime,
+}
+
+/// Types of modulation available for transmitting and receiving packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModulationMode {
+    /// Regular digital frequency shift keying mode
+    Fsk,
+    /// Gaussian frequency shift keying mode, with the given Gaussian filter
+    /// bandwidth-time product
+    Gfsk(GaussianBt),
+    /// LoRa chirp spread spectrum mode
+    LoRa,
+}
+
+/// Bandwidth-time products of the Gaussian filter applied in GFSK mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GaussianBt {
+    /// No filtering; plain FSK.
+    None,
+    /// BT = 1.0
+    Bt1_0,
+    /// BT = 0.5
+    Bt0_5,
+    /// BT = 0.3
+    Bt0_3,
+}
+
+impl GaussianBt {
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            GaussianBt::None => "none",
+            GaussianBt::Bt1_0 => "1.0",
+            GaussianBt::Bt0_5 => "0.5",
+            GaussianBt::Bt0_3 => "0.3",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "none" => Some(GaussianBt::None),
+            "1.0" => Some(GaussianBt::Bt1_0),
+            "0.5" => Some(GaussianBt::Bt0_5),
+            "0.3" => Some(GaussianBt::Bt0_3),
+            _ => None,
+        }
+    }
+}
+
+/// Receiver and automatic frequency correction bandwidths available in FSK mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FskBandwidth {
+    /// 2.6 kHz
+    Khz2_6,
+    /// 3.1 kHz
+    Khz3_1,
+    /// 3.9 kHz
+    Khz3_9,
+    /// 5.2 kHz
+    Khz5_2,
+    /// 6.3 kHz
+    Khz6_3,
+    /// 7.8 kHz
+    Khz7_8,
+    /// 10.4 kHz
+    Khz10_4,
+    /// 12.5 kHz
+    Khz12_5,
+    /// 15.6 kHz
+    Khz15_6,
+    /// 20.8 kHz
+    Khz20_8,
+    /// 25 kHz
+    Khz25,
+    /// 31.3 kHz
+    Khz31_3,
+    /// 41.7 kHz
+    Khz41_7,
+    /// 50 kHz
+    Khz50,
+    /// 62.5 kHz
+    Khz62_5,
+    /// 83.3 kHz
+    Khz83_3,
+    /// 100 kHz
+    Khz100,
+    /// 125 kHz
+    Khz125,
+    /// 166.7 kHz
+    Khz166_7,
+    /// 200 kHz
+    Khz200,
+    /// 250 kHz
+    Khz250,
+}
+
+/// Every `FskBandwidth`, with the way the module writes it and its value in Hz.
+const FSK_BANDWIDTHS: &[(FskBandwidth, &str, u32)] = &[
+    (FskBandwidth::Khz2_6, "2.6", 2_600),
+    (FskBandwidth::Khz3_1, "3.1", 3_100),
+    (FskBandwidth::Khz3_9, "3.9", 3_900),
+    (FskBandwidth::Khz5_2, "5.2", 5_200),
+    (FskBandwidth::Khz6_3, "6.3", 6_300),
+    (FskBandwidth::Khz7_8, "7.8", 7_800),
+    (FskBandwidth::Khz10_4, "10.4", 10_400),
+    (FskBandwidth::Khz12_5, "12.5", 12_500),
+    (FskBandwidth::Khz15_6, "15.6", 15_600),
+    (FskBandwidth::Khz20_8, "20.8", 20_800),
+    (FskBandwidth::Khz25, "25", 25_000),
+    (FskBandwidth::Khz31_3, "31.3", 31_300),
+    (FskBandwidth::Khz41_7, "41.7", 41_700),
+    (FskBandwidth::Khz50, "50", 50_000),
+    (FskBandwidth::Khz62_5, "62.5", 62_500),
+    (FskBandwidth::Khz83_3, "83.3", 83_300),
+    (FskBandwidth::Khz100, "100", 100_000),
+    (FskBandwidth::Khz125, "125", 125_000),
+    (FskBandwidth::Khz166_7, "166.7", 166_700),
+    (FskBandwidth::Khz200, "200", 200_000),
+    (FskBandwidth::Khz250, "250", 250_000),
+];
+
+impl FskBandwidth {
+    /// Returns the bandwidth in Hz, rounded to the nearest 100 Hz.
+    pub fn hz(self) -> u32 {
+        self.entry().2
+    }
+
+    fn entry(self) -> &'static (FskBandwidth, &'static str, u32) {
+        FSK_BANDWIDTHS
+            .iter()
+            .find(|(bw, _, _)| *bw == self)
+            .expect("every FskBandwidth is listed in FSK_BANDWIDTHS")
+    }
+
+    pub(crate) fn token(self) -> &'static str {
+        self.entry().1
+    }
+
+    /// Parses the bandwidth in kHz as the module reports it, which may or may not include
+    /// a trailing `.0`.
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        let khz: f32 = token.parse().ok()?;
+        let hz = (khz * 1000.0).round() as u32;
+        FSK_BANDWIDTHS
+            .iter()
+            .find(|(_, _, h)| *h == hz)
+            .map(|(bw, _, _)| *bw)
+    }
+}
+
+/// LoRa spreading factors, trading data rate for range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpreadingFactor {
+    /// 128 chips per symbol; the fastest and shortest-range setting.
+    Sf7,
+    /// 256 chips per symbol.
+    Sf8,
+    /// 512 chips per symbol.
+    Sf9,
+    /// 1024 chips per symbol.
+    Sf10,
+    /// 2048 chips per symbol.
+    Sf11,
+    /// 4096 chips per symbol; the slowest and longest-range setting.
+    Sf12,
+}
+
+impl SpreadingFactor {
+    /// Returns the spreading factor as a number, like 7 for `Sf7`.
+    pub fn value(self) -> u8 {
+        match self {
+            SpreadingFactor::Sf7 => 7,
+            SpreadingFactor::Sf8 => 8,
+            SpreadingFactor::Sf9 => 9,
+            SpreadingFactor::Sf10 => 10,
+            SpreadingFactor::Sf11 => 11,
+            SpreadingFactor::Sf12 => 12,
+        }
+    }
+
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            SpreadingFactor::Sf7 => "sf7",
+            SpreadingFactor::Sf8 => "sf8",
+            SpreadingFactor::Sf9 => "sf9",
+            SpreadingFactor::Sf10 => "sf10",
+            SpreadingFactor::Sf11 => "sf11",
+            SpreadingFactor::Sf12 => "sf12",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "sf7" => Some(SpreadingFactor::Sf7),
+            "sf8" => Some(SpreadingFactor::Sf8),
+            "sf9" => Some(SpreadingFactor::Sf9),
+            "sf10" => Some(SpreadingFactor::Sf10),
+            "sf11" => Some(SpreadingFactor::Sf11),
+            "sf12" => Some(SpreadingFactor::Sf12),
+            _ => None,
+        }
+    }
+}
+
+/// LoRa signal bandwidths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bandwidth {
+    /// 125 kHz
+    Khz125,
+    /// 250 kHz
+    Khz250,
+    /// 500 kHz
+    Khz500,
+}
+
+impl Bandwidth {
+    /// Returns the bandwidth in Hz.
+    pub fn hz(self) -> u32 {
+        match self {
+            Bandwidth::Khz125 => 125_000,
+            Bandwidth::Khz250 => 250_000,
+            Bandwidth::Khz500 => 500_000,
+        }
+    }
+
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            Bandwidth::Khz125 => "125",
+            Bandwidth::Khz250 => "250",
+            Bandwidth::Khz500 => "500",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "125" => Some(Bandwidth::Khz125),
+            "250" => Some(Bandwidth::Khz250),
+            "500" => Some(Bandwidth::Khz500),
+            _ => None,
+        }
+    }
+}
+
+/// LoRa forward error correction coding rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CodingRate {
+    /// 4/5: four data bits for every five bits sent.
+    Cr4_5,
+    /// 4/6: four data bits for every six bits sent.
+    Cr4_6,
+    /// 4/7: four data bits for every seven bits sent.
+    Cr4_7,
+    /// 4/8: four data bits for every eight bits sent.
+    Cr4_8,
+}
+
+impl CodingRate {
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            CodingRate::Cr4_5 => "4/5",
+            CodingRate::Cr4_6 => "4/6",
+            CodingRate::Cr4_7 => "4/7",
+            CodingRate::Cr4_8 => "4/8",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "4/5" => Some(CodingRate::Cr4_5),
+            "4/6" => Some(CodingRate::Cr4_6),
+            "4/7" => Some(CodingRate::Cr4_7),
+            "4/8" => Some(CodingRate::Cr4_8),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the `on` or `off` reported for boolean radio settings.
+pub(crate) fn parse_on_off(value: &str) -> Option<bool> {
+    match value {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Formats a boolean radio setting as `on` or `off`.
+pub(crate) fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// # Radio API Functions
+///
+/// The `radio_set_*` and `radio_get_*` functions configure the radio used by
+/// [`::radio_rx()`](#method.radio_rx) and [`::radio_transmit()`](#method.radio_transmit).
+/// Settings are lost when the module is reset. Values the module rejects result in
+/// `Error::InvalidParam`, and values which could never be valid are rejected with
+/// `Error::OutOfRange` without being sent.
+///
+/// # Example
+///
+/// Configuring the radio for SF7 at 125 kHz on 915 MHz.
+///
+/// ```
+/// # use rn2903::{Bandwidth, Rn2903, SpreadingFactor};
+/// # use rn2903::sim::Simulator;
+/// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+/// txvr.mac_pause().unwrap();
+/// txvr.radio_set_frequency(915_000_000).unwrap();
+/// txvr.radio_set_spreading_factor(SpreadingFactor::Sf7).unwrap();
+/// txvr.radio_set_bandwidth(Bandwidth::Khz125).unwrap();
+/// assert_eq!(txvr.radio_get_frequency().unwrap(), 915_000_000);
+/// assert_eq!(txvr.radio_get_spreading_factor().unwrap(), SpreadingFactor::Sf7);
+/// assert!(txvr.radio_set_frequency(100_000_000).is_err());
+/// ```
+impl<T: Transport> Rn2903<T> {
+    /// Set the modulation mode used by the radio for transmission and reception.
+    ///
+    /// Selecting `Fsk` or `Gfsk` also sets the Gaussian filter used in FSK mode, with
+    /// `Fsk` disabling it.
+    pub fn radio_set_modulation_mode(&mut self, mode: ModulationMode) -> Result<()> {
+        self.run(operation::SetModulation::new(mode))
+    }
+
+    /// Queries the modulation mode used by the radio for transmission and reception.
+    ///
+    /// In FSK mode, this also queries the Gaussian filter to distinguish `Fsk` from
+    /// `Gfsk`. The mode is remembered, so transmitting and receiving need not query it
+    /// again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{ModulationMode, Rn2903};
+    /// # use rn2903::mock::MockTransport;
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"radio get mod", &[b"lora"]);
+    /// mock.expect(b"radio get wdt", &[b"15000"]);
+    /// mock.expect(b"radio tx 00", &[b"ok", b"radio_tx_ok"]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    ///
+    /// assert_eq!(txvr.radio_get_modulation_mode().unwrap(), ModulationMode::LoRa);
+    /// txvr.radio_transmit(&[0x00]).unwrap();
+    /// txvr.port().assert_finished();
+    /// ```
+    pub fn radio_get_modulation_mode(&mut self) -> Result<ModulationMode> {
+        self.run(operation::GetModulation::new())
+    }
+
+    /// Sets the LoRa spreading factor.
+    pub fn radio_set_spreading_factor(&mut self, sf: SpreadingFactor) -> Result<()> {
+        self.run(operation::set_spreading_factor(sf))
+    }
+
+    /// Queries the LoRa spreading factor.
+    pub fn radio_get_spreading_factor(&mut self) -> Result<SpreadingFactor> {
+        self.run(operation::get_spreading_factor())
+    }
+
+    /// Sets the LoRa signal bandwidth.
+    pub fn radio_set_bandwidth(&mut self, bw: Bandwidth) -> Result<()> {
+        self.run(operation::set_bandwidth(bw))
+    }
+
+    /// Queries the LoRa signal bandwidth.
+    pub fn radio_get_bandwidth(&mut self) -> Result<Bandwidth> {
+        self.run(operation::get_bandwidth())
+    }
+
+    /// Sets the LoRa coding rate.
+    pub fn radio_set_coding_rate(&mut self, cr: CodingRate) -> Result<()> {
+        self.request(protocol::radio_set_coding_rate(cr))
+    }
+
+    /// Queries the LoRa coding rate.
+    pub fn radio_get_coding_rate(&mut self) -> Result<CodingRate> {
+        self.request(protocol::radio_get_coding_rate())
+    }
+
+    /// Sets the carrier frequency in Hz.
+    ///
+    /// Frequencies outside the bands of the connected module's
+    /// [regions](region/index.html) are rejected with `Error::OutOfRange`. If the model
+    /// has not been detected, as after `::new_unchecked()`, the bands of both modules are
+    /// accepted.
+    pub fn radio_set_frequency(&mut self, hz: u32) -> Result<()> {
+        self.run(operation::set_frequency(self.session.model, hz)?)
+    }
+
+    /// Queries the carrier frequency in Hz.
+    pub fn radio_get_frequency(&mut self) -> Result<u32> {
+        self.run(operation::get_frequency())
+    }
+
+    /// Queries the signal-to-noise ratio, in dB, of the last packet received in LoRa
+    /// mode.
+    pub fn radio_get_snr(&mut self) -> Result<i8> {
+        self.request(protocol::radio_get_snr())
+    }
+
+    /// Queries the received signal strength, in dBm, of the last packet received.
+    ///
+    /// Returns `Ok(None)` if the firmware does not support this query, which was added in
+    /// firmware version 1.0.5.
+    pub fn radio_get_packet_rssi(&mut self) -> Result<Option<i16>> {
+        self.request(protocol::radio_get_packet_rssi())
+    }
+
+    /// Sets the output power in dBm.
+    ///
+    /// Powers the connected module does not support, -3 to 15 dBm for the RN2483 and 2
+    /// to 20 dBm for the RN2903, are rejected with `Error::OutOfRange`. If the model has
+    /// not been detected, powers from -3 to 20 dBm are accepted.
+    pub fn radio_set_power(&mut self, dbm: i8) -> Result<()> {
+        self.request(protocol::radio_set_power(self.session.model, dbm)?)
+    }
+
+    /// Queries the output power in dBm.
+    pub fn radio_get_power(&mut self) -> Result<i8> {
+        self.request(protocol::radio_get_power())
+    }
+
+    /// Sets the preamble length in symbols.
+    pub fn radio_set_preamble_length(&mut self, symbols: u16) -> Result<()> {
+        self.request(protocol::radio_set_preamble_length(symbols))
+    }
+
+    /// Queries the preamble length in symbols.
+    pub fn radio_get_preamble_length(&mut self) -> Result<u16> {
+        self.request(protocol::radio_get_preamble_length())
+    }
+
+    /// Enables or disables the CRC header on transmitted packets.
+    pub fn radio_set_crc(&mut self, enabled: bool) -> Result<()> {
+        self.request(protocol::radio_set_crc(enabled))
+    }
+
+    /// Queries whether the CRC header is enabled.
+    pub fn radio_get_crc(&mut self) -> Result<bool> {
+        self.request(protocol::radio_get_crc())
+    }
+
+    /// Enables or disables inversion of the I and Q signals.
+    pub fn radio_set_iq_inversion(&mut self, enabled: bool) -> Result<()> {
+        self.request(protocol::radio_set_iq_inversion(enabled))
+    }
+
+    /// Queries whether inversion of the I and Q signals is enabled.
+    pub fn radio_get_iq_inversion(&mut self) -> Result<bool> {
+        self.request(protocol::radio_get_iq_inversion())
+    }
+
+    /// Sets the LoRa sync word. Public LoRaWAN networks use 0x34.
+    ///
+    /// In FSK mode, use [`::radio_set_fsk_sync_word()`](#method.radio_set_fsk_sync_word)
+    /// instead.
+    pub fn radio_set_sync_word(&mut self, sync: u8) -> Result<()> {
+        self.request(protocol::radio_set_sync_word(sync))
+    }
+
+    /// Queries the LoRa sync word.
+    pub fn radio_get_sync_word(&mut self) -> Result<u8> {
+        self.request(protocol::radio_get_sync_word())
+    }
+
+    /// Sets the FSK bit rate in bits per second, between 1 and 300000.
+    pub fn radio_set_fsk_bitrate(&mut self, bps: u32) -> Result<()> {
+        self.request(protocol::radio_set_fsk_bitrate(bps)?)
+    }
+
+    /// Queries the FSK bit rate in bits per second.
+    pub fn radio_get_fsk_bitrate(&mut self) -> Result<u32> {
+        self.request(protocol::radio_get_fsk_bitrate())
+    }
+
+    /// Sets the FSK frequency deviation in Hz, between 0 and 200000.
+    pub fn radio_set_frequency_deviation(&mut self, hz: u32) -> Result<()> {
+        self.request(protocol::radio_set_frequency_deviation(hz)?)
+    }
+
+    /// Queries the FSK frequency deviation in Hz.
+    pub fn radio_get_frequency_deviation(&mut self) -> Result<u32> {
+        self.request(protocol::radio_get_frequency_deviation())
+    }
+
+    /// Sets the FSK receiver signal bandwidth.
+    pub fn radio_set_rx_bandwidth(&mut self, bw: FskBandwidth) -> Result<()> {
+        self.request(protocol::radio_set_rx_bandwidth(bw))
+    }
+
+    /// Queries the FSK receiver signal bandwidth.
+    pub fn radio_get_rx_bandwidth(&mut self) -> Result<FskBandwidth> {
+        self.request(protocol::radio_get_rx_bandwidth())
+    }
+
+    /// Sets the FSK automatic frequency correction bandwidth.
+    pub fn radio_set_afc_bandwidth(&mut self, bw: FskBandwidth) -> Result<()> {
+        self.request(protocol::radio_set_afc_bandwidth(bw))
+    }
+
+    /// Queries the FSK automatic frequency correction bandwidth.
+    pub fn radio_get_afc_bandwidth(&mut self) -> Result<FskBandwidth> {
+        self.request(protocol::radio_get_afc_bandwidth())
+    }
+
+    /// Sets the FSK sync word, which must be between 1 and 8 bytes long.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{GaussianBt, ModulationMode, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// txvr.radio_set_modulation_mode(ModulationMode::Gfsk(GaussianBt::Bt0_5)).unwrap();
+    /// txvr.radio_set_fsk_bitrate(50_000).unwrap();
+    /// txvr.radio_set_fsk_sync_word(&[0xC1, 0x94, 0xC1]).unwrap();
+    /// assert_eq!(txvr.radio_get_fsk_sync_word().unwrap(), vec![0xC1, 0x94, 0xC1]);
+    /// assert_eq!(
+    ///     txvr.radio_get_modulation_mode().unwrap(),
+    ///     ModulationMode::Gfsk(GaussianBt::Bt0_5)
+    /// );
+    /// ```
+    pub fn radio_set_fsk_sync_word(&mut self, sync: &[u8]) -> Result<()> {
+        self.request(protocol::radio_set_fsk_sync_word(sync)?)
+    }
+
+    /// Queries the FSK sync word.
+    pub fn radio_get_fsk_sync_word(&mut self) -> Result<Vec<u8>> {
+        self.request(protocol::radio_get_fsk_sync_word())
+    }
+
+    /// Sets the radio watchdog timeout in milliseconds, after which any reception or
+    /// transmission is abandoned. A value of 0 disables the watchdog.
+    ///
+    /// The watchdog also bounds how long [`::radio_rx()`](#method.radio_rx) and
+    /// [`::radio_transmit()`](#method.radio_transmit) wait for the module to report on a
+    /// reception or transmission. With it disabled, they instead wait as long as the
+    /// reception or transmission can take at the current radio settings, which are
+    /// queried if need be, except that `radio_rx(0)` waits as long as it takes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// use rn2903::mock::MockTransport;
+    ///
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"radio set wdt 0", &[b"ok"]);
+    /// mock.expect(b"radio get mod", &[b"lora"]);
+    /// mock.expect(b"radio get sf", &[b"sf7"]);
+    /// mock.expect(b"radio get bw", &[b"125"]);
+    /// mock.expect(b"radio get prlen", &[b"8"]);
+    /// mock.expect(b"radio rx 100", &[b"ok", b"radio_err"]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    ///
+    /// txvr.radio_set_watchdog_timeout(0).unwrap();
+    /// // Waits for 100 symbols and the longest packet at SF7 and 125 kHz, about 0.5 s.
+    /// assert_eq!(txvr.radio_rx(100).unwrap(), None);
+    /// txvr.port().assert_finished();
+    /// ```
+    pub fn radio_set_watchdog_timeout(&mut self, ms: u32) -> Result<()> {
+        self.run(operation::set_watchdog_timeout(ms))
+    }
+
+    /// Queries the radio watchdog timeout in milliseconds.
+    pub fn radio_get_watchdog_timeout(&mut self) -> Result<u32> {
+        self.run(operation::get_watchdog_timeout())
+    }
+
+    /// Open the receiver for the given timeout in symbols (for LoRa) or milliseconds
+    /// (for FSK), returning `Ok(Some(_))` if a valid packet is received or `Ok(None)` if
+    /// no packet is received before the timeout.
+    ///
+    /// A timeout of 0 keeps the receiver open until a packet arrives or the radio
+    /// watchdog expires. Either way, the module reports on the reception by the time the
+    /// watchdog expires, so if it has not, shortly after, this fails with
+    /// `Error::Timeout`. The watchdog timeout is queried first unless it has been set or
+    /// queried since the last reset. With the watchdog disabled, the wait is instead
+    /// bounded by the timeout and the longest packet at the current radio settings, as
+    /// [`::radio_set_watchdog_timeout()`](#method.radio_set_watchdog_timeout) describes,
+    /// and a timeout of 0 waits as long as it takes. A reply which is not a well-formed
+    /// `radio_rx` or `radio_err` line fails with `Error::BadResponse`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Error, Rn2903};
+    /// use rn2903::mock::MockTransport;
+    ///
+    /// let mut mock = MockTransport::new();
+    /// mock.expect(b"radio get wdt", &[b"15000"]);
+    /// mock.expect(b"radio rx 100", &[b"ok", b"radio_rx 4869"]);
+    /// mock.expect(b"radio rx 100", &[b"ok", b"radio_rx"]);
+    /// let mut txvr = Rn2903::new_unchecked(mock);
+    /// assert_eq!(txvr.radio_rx(100).unwrap(), Some(b"Hi".to_vec()));
+    /// match txvr.radio_rx(100) {
+    ///     Err(Error::BadResponse(_, response)) => assert_eq!(response, "radio_rx"),
+    ///     other => panic!("unexpected {:?}", other),
+    /// }
+    /// ```
+    pub fn radio_rx(&mut self, timeout: u16) -> Result<Option<Vec<u8>>> {
+        self.run(operation::RadioRx::new(timeout))
+    }
+
+    /// Opens the receiver for the given timeout, without waiting for the outcome.
+    pub(crate) fn radio_rx_start(&mut self, timeout: u16) -> Result<()> {
+        self.request(protocol::radio_rx(timeout))
+    }
+
+    /// Closes the receiver if it is open.
+    pub fn radio_rx_stop(&mut self) -> Result<()> {
+        self.request(protocol::radio_rx_stop())
+    }
+
+    /// Transmits the given payload, returning when the module reports that the
+    /// transmission has finished.
+    ///
+    /// Payloads longer than the module accepts in the current modulation mode (255 bytes
+    /// for LoRa, 64 bytes for FSK) are rejected with `Error::OutOfRange` without being
+    /// sent. In LoRa mode, the limit is the same at every spreading factor and bandwidth,
+    /// which only change how long the packet takes to send. The MAC must be paused
+    /// first. As with [`::radio_rx()`](#method.radio_rx), the radio watchdog, or with it
+    /// disabled the time the packet takes on air, bounds how long this waits for the
+    /// transmission to finish.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// txvr.mac_pause().unwrap();
+    /// let transmission = txvr.radio_transmit(&[0x00, 0xFF, 0x80]).unwrap();
+    /// println!("Sent in about {:?}", transmission.duration());
+    /// assert_eq!(txvr.port().transmitted(), &[vec![0x00, 0xFF, 0x80]]);
+    /// assert!(txvr.radio_transmit(&[0; 256]).is_err());
+    /// ```
+    pub fn radio_transmit(&mut self, payload: &[u8]) -> Result<Transmission> {
+        self.run(operation::RadioTransmit::new(payload))
+    }
+
+    /// Transmits the given string, as
+    /// [`::radio_transmit()`](#method.radio_transmit) does. Always returns `Ok(None)`
+    /// on success.
+    #[deprecated(since = "0.3.0", note = "use `radio_transmit`, which takes bytes")]
+    pub fn radio_tx(&mut self, send: String) -> Result<Option<Vec<u8>>> {
+        self.radio_transmit(send.as_bytes()).map(|_| None)
+    }
+
+    /// Open the receiver for the given timeout, as [`::radio_rx()`](#method.radio_rx)
+    /// does, returning the packet along with its signal quality and the radio settings
+    /// it was received with.
+    ///
+    /// The signal quality is queried from the module after the packet arrives. Radio
+    /// settings which have been set or queried through this crate are not queried again,
+    /// unless the module has been reset, the MAC has joined, transmitted, or resumed, or
+    /// a raw command has been sent since.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::{Rn2903, SpreadingFactor};
+    /// # use rn2903::sim::Simulator;
+    /// let mut sim = Simulator::new();
+    /// sim.queue_packet_with_quality(b"ping", -3, -112);
+    /// let mut txvr = Rn2903::new(sim).unwrap();
+    /// txvr.mac_pause().unwrap();
+    /// txvr.radio_set_spreading_factor(SpreadingFactor::Sf9).unwrap();
+    ///
+    /// let packet = txvr.radio_receive(100).unwrap().expect("a packet");
+    /// assert_eq!(packet.payload, b"ping");
+    /// assert_eq!(packet.snr, Some(-3));
+    /// assert_eq!(packet.rssi, Some(-112));
+    /// assert_eq!(packet.spreading_factor, Some(SpreadingFactor::Sf9));
+    /// ```
+    pub fn radio_receive(&mut self, timeout: u16) -> Result<Option<ReceivedPacket>> {
+        self.run(operation::RadioReceive::new(timeout))
+    }
+
+    /// Gathers the signal quality and radio settings for a packet which has just been
+    /// received.
+    pub(crate) fn radio_packet_info(
+        &mut self,
+        payload: Vec<u8>,
+        received_at: SystemTime,
+    ) -> Result<ReceivedPacket> {
+        self.run(operation::PacketInfo::new(payload, received_at))
+    }
+}
@@ -0,0 +1,287 @@
+//! Continuous reception of packets as an iterator.
+
+use crate::operation;
+use crate::protocol;
+use crate::{Error, ReceivedPacket, Result, Rn2903, Transport};
+use core::time::Duration;
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+/// How long before the watchdog timeout a `radio_err` is still taken to be the watchdog
+/// closing the receiver, allowing for the time taken to open it.
+const WATCHDOG_MARGIN: Duration = Duration::from_millis(100);
+
+/// How long to wait before reopening the receiver after the first failure to do so.
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The longest wait before reopening the receiver after repeated failures.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// An iterator which keeps the radio listening and yields every packet it receives.
+///
+/// Created by [`Rn2903::receive_iter()`](struct.Rn2903.html#method.receive_iter). The
+/// receiver is opened in continuous mode and reopened after every packet, and whenever
+/// the radio watchdog closes it, so the iterator never ends on its own. Errors are
+/// yielded as they occur; iteration may continue after them, for instance after a
+/// malformed notification.
+///
+/// A `radio_err` which arrives before the watchdog could have expired, or at all when
+/// the watchdog is disabled, is yielded as `Error::ReceptionUnsuccessful` rather than
+/// silently reopening the receiver. After that error, or `Error::TransceiverBusy` when
+/// opening the receiver, the next call waits before trying again, for twice as long
+/// after each consecutive failure up to a second.
+///
+/// While the watchdog is enabled, `Error::Timeout` is yielded if the receiver has been
+/// open for longer than it allows, plus the response timeout and a margin, without the
+/// module reporting anything. The receiver is closed, and the next call opens it again.
+/// With the watchdog disabled, each call waits for a packet for as long as it takes.
+///
+/// Call [`::stop()`](#method.stop) to close the receiver once finished. Dropping the
+/// `Receiver` also closes it, but ignores any error in doing so, and any packet received
+/// as it closes.
+pub struct Receiver<'a, T: Transport> {
+    txvr: &'a mut Rn2903<T>,
+    listening: bool,
+    opened_at: Instant,
+    deadline: Option<Instant>,
+    retry_delay: Option<Duration>,
+}
+
+impl<'a, T: Transport> Receiver<'a, T> {
+    /// Closes the receiver on the module, if it is open, and releases the `Rn2903`.
+    ///
+    /// A packet whose reception finishes just as the receiver closes is returned, rather
+    /// than lost.
+    pub fn stop(mut self) -> Result<Option<ReceivedPacket>> {
+        match self.close()? {
+            Some(payload) => {
+                let received_at = SystemTime::now();
+                self.txvr.radio_packet_info(payload, received_at).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Sends `radio rxstop` if the receiver is open, returning the payload of a packet
+    /// received as it closed.
+    fn close(&mut self) -> Result<Option<Vec<u8>>> {
+        if !self.listening {
+            return Ok(None);
+        }
+        self.listening = false;
+        self.txvr.run(operation::RadioRxStop::new())
+    }
+
+    /// Waits before reopening the receiver next time, for longer than last time.
+    fn back_off(&mut self) {
+        self.retry_delay = Some(match self.retry_delay {
+            Some(delay) => (delay * 2).min(MAX_RETRY_DELAY),
+            None => MIN_RETRY_DELAY,
+        });
+    }
+
+    /// Whether the radio watchdog could have closed the receiver by now.
+    fn watchdog_expired(&mut self) -> Result<bool> {
+        let ms = self
+            .txvr
+            .run(operation::get_watchdog_timeout().unless_known())?;
+        Ok(match ms {
+            0 => false,
+            ms => self.opened_at.elapsed() + WATCHDOG_MARGIN >= Duration::from_millis(ms.into()),
+        })
+    }
+
+    /// Opens the receiver, noting when the module must have reported on it by.
+    fn open(&mut self) -> Result<()> {
+        let wait = self.txvr.run(operation::RadioWait::new())?;
+        self.txvr.radio_rx_start(0)?;
+        self.listening = true;
+        self.opened_at = Instant::now();
+        self.deadline = wait.map(|wait| self.opened_at + self.txvr.response_timeout + wait);
+        Ok(())
+    }
+}
+
+impl<'a, T: Transport> Iterator for Receiver<'a, T> {
+    type Item = Result<ReceivedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.listening {
+                if let Some(delay) = self.retry_delay {
+                    thread::sleep(delay);
+                }
+                match self.open() {
+                    Ok(()) => {}
+                    Err(Error::TransceiverBusy) => {
+                        self.back_off();
+                        return Some(Err(Error::TransceiverBusy));
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let line = match self.txvr.read_line_until(self.deadline) {
+                Ok(line) => line,
+                Err(e) => {
+                    if let Error::Timeout { .. } = e {
+                        // The module has not reported on the reception when it must have;
+                        // start afresh with the next call.
+                        let _ = self.close();
+                    }
+                    return Some(Err(e));
+                }
+            };
+            self.listening = false;
+            match protocol::radio_rx_result(&line) {
+                Ok(None) => match self.watchdog_expired() {
+                    // The watchdog closed the receiver; open it again.
+                    Ok(true) => self.retry_delay = None,
+                    Ok(false) => {
+                        self.back_off();
+                        return Some(Err(Error::ReceptionUnsuccessful));
+                    }
+                    Err(e) => return Some(Err(e)),
+                },
+                Ok(Some(payload)) => {
+                    self.retry_delay = None;
+                    let received_at = SystemTime::now();
+                    return Some(self.txvr.radio_packet_info(payload, received_at));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl<'a, T: Transport> Drop for Receiver<'a, T> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// # Continuous Reception
+impl<T: Transport> Rn2903<T> {
+    /// Returns an iterator which keeps the radio listening and yields every packet it
+    /// receives, with its signal quality and radio settings as
+    /// [`::radio_receive()`](#method.radio_receive) reports them.
+    ///
+    /// The MAC must be paused first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rn2903::Rn2903;
+    /// # use rn2903::sim::Simulator;
+    /// let mut sim = Simulator::new();
+    /// sim.queue_packet(b"one");
+    /// sim.queue_packet(b"two");
+    /// let mut txvr = Rn2903::new(sim).unwrap();
+    /// txvr.mac_pause().unwrap();
+    ///
+    /// let mut receiver = txvr.receive_iter();
+    /// for packet in receiver.by_ref().take(2) {
+    ///     let packet = packet.unwrap();
+    ///     println!("{:?} at {} dB SNR", packet.payload, packet.snr.unwrap());
+    /// }
+    /// receiver.stop().unwrap();
+    /// ```
+    ///
+    /// The simulator reports `radio_err` at once when it has no packet to deliver, long
+    /// before the watchdog timeout, so the receiver yields an error:
+    ///
+    /// ```
+    /// # use rn2903::{Error, Rn2903};
+    /// # use rn2903::sim::Simulator;
+    /// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+    /// txvr.mac_pause().unwrap();
+    ///
+    /// let mut receiver = txvr.receive_iter();
+    /// match receiver.next() {
+    ///     Some(Err(Error::ReceptionUnsuccessful)) => {}
+    ///     other => panic!("unexpected {:?}", other),
+    /// }
+    /// ```
+    pub fn receive_iter(&mut self) -> Receiver<'_, T> {
+        Receiver {
+            txvr: self,
+            listening: false,
+            opened_at: Instant::now(),
+            deadline: None,
+            retry_delay: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::MockTransport;
+    use crate::{Rn2903, SpreadingFactor};
+
+    /// Expects the receiver to be opened, then closed with the given replies.
+    fn open_then_stop(mock: &mut MockTransport, replies: &[&[u8]]) {
+        mock.expect(b"radio rx 0", &[b"ok"]);
+        mock.expect(b"radio rxstop", replies);
+    }
+
+    #[test]
+    fn stop_returns_a_packet_reported_before_the_ok() {
+        let mut mock = MockTransport::new();
+        mock.expect(b"radio get wdt", &[b"15000"]);
+        open_then_stop(&mut mock, &[b"radio_rx  BEEF", b"ok"]);
+        mock.expect(b"radio get mod", &[b"lora"]);
+        mock.expect(b"radio get snr", &[b"5"]);
+        mock.expect(b"radio get pktrssi", &[b"-90"]);
+        mock.expect(b"radio get freq", &[b"915000000"]);
+        mock.expect(b"radio get sf", &[b"sf7"]);
+        mock.expect(b"radio get bw", &[b"125"]);
+        mock.expect(b"radio get sf", &[b"sf9"]);
+        let mut txvr = Rn2903::new_unchecked(mock);
+
+        let mut receiver = txvr.receive_iter();
+        receiver.open().unwrap();
+        let packet = receiver.stop().unwrap().unwrap();
+        assert_eq!(packet.payload, vec![0xBE, 0xEF]);
+        assert_eq!(packet.snr, Some(5));
+
+        assert_eq!(
+            txvr.radio_get_spreading_factor().unwrap(),
+            SpreadingFactor::Sf9
+        );
+        txvr.port().assert_finished();
+    }
+
+    #[test]
+    fn stop_consumes_an_outcome_reported_after_the_ok() {
+        let mut mock = MockTransport::new();
+        mock.expect(b"radio get wdt", &[b"15000"]);
+        open_then_stop(&mut mock, &[b"ok", b"radio_err"]);
+        open_then_stop(&mut mock, &[b"ok", b"radio_rx  CAFE"]);
+        mock.expect(b"radio get mod", &[b"fsk"]);
+        mock.expect(b"radio get bt", &[b"none"]);
+        mock.expect(b"radio get pktrssi", &[b"-70"]);
+        mock.expect(b"radio get freq", &[b"915000000"]);
+        open_then_stop(&mut mock, &[b"ok"]);
+        mock.expect(b"radio get sf", &[b"sf9"]);
+        let mut txvr = Rn2903::new_unchecked(mock);
+
+        let mut receiver = txvr.receive_iter();
+        receiver.open().unwrap();
+        assert!(receiver.stop().unwrap().is_none());
+
+        let mut receiver = txvr.receive_iter();
+        receiver.open().unwrap();
+        let packet = receiver.stop().unwrap().unwrap();
+        assert_eq!(packet.payload, vec![0xCA, 0xFE]);
+
+        let mut receiver = txvr.receive_iter();
+        receiver.open().unwrap();
+        assert!(receiver.stop().unwrap().is_none());
+
+        assert_eq!(
+            txvr.radio_get_spreading_factor().unwrap(),
+            SpreadingFactor::Sf9
+        );
+        txvr.port().assert_finished();
+    }
+}
@@ -0,0 +1,351 @@
+//! The regional frequency plans the RN2483 and RN2903 are built for.
+//!
+//! Each [`Region`](enum.Region.html) describes the band a module may transmit in, the
+//! channels its LoRaWAN plan fixes and the data rates allowed on them, the maximum EIRP,
+//! and the duty-cycle limits of its sub-bands. The RN2483 covers
+//! [`Eu868`](enum.Region.html#variant.Eu868) and [`Eu433`](enum.Region.html#variant.Eu433),
+//! and the RN2903 [`Us915`](enum.Region.html#variant.Us915) and
+//! [`Au915`](enum.Region.html#variant.Au915).
+//!
+//! Once the model has been detected, by `Rn2903::new()` or
+//! `::system_firmware_version()`, frequencies outside its regions are rejected with
+//! `Error::OutOfRange` without being sent, as are output powers it does not support.
+//!
+//! # Examples
+//!
+//! ```
+//! # use rn2903::{Bandwidth, Error, Model, Rn2903, SpreadingFactor};
+//! # use rn2903::sim::Simulator;
+//! use rn2903::region::{DataRate, Region};
+//!
+//! let us915 = Region::Us915;
+//! let channel = us915.channel(64).unwrap();
+//! assert_eq!(channel.frequency, 903_000_000);
+//! assert_eq!(
+//!     us915.data_rate(channel.max_data_rate),
+//!     Some(DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz500))
+//! );
+//! assert_eq!(Region::Eu868.sub_band(869_525_000).unwrap().duty_cycle_permille, 100);
+//!
+//! let mut txvr = Rn2903::new(Simulator::rn2483()).unwrap();
+//! assert_eq!(txvr.model().unwrap().regions(), &[Region::Eu868, Region::Eu433]);
+//! txvr.mac_pause().unwrap();
+//! txvr.radio_set_frequency(868_100_000).unwrap();
+//! assert!(matches!(
+//!     txvr.radio_set_frequency(915_000_000),
+//!     Err(Error::OutOfRange { .. })
+//! ));
+//! assert!(txvr.port().commands().iter().all(|c| c != "radio set freq 915000000"));
+//! ```
+
+use crate::{Bandwidth, Error, Model, Result, SpreadingFactor};
+use core::ops::RangeInclusive;
+
+/// The output powers, in dBm, accepted by either module, for when the model is unknown.
+const POWER_RANGE: RangeInclusive<i8> = -3..=20;
+
+/// A regional frequency plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// The European 868 MHz plan, served by the RN2483.
+    Eu868,
+    /// The European 433 MHz plan, served by the RN2483.
+    Eu433,
+    /// The North American 915 MHz plan, served by the RN2903.
+    Us915,
+    /// The Australian 915 MHz plan, served by the RN2903.
+    Au915,
+}
+
+/// A channel fixed by a regional plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channel {
+    /// The channel's index, as used by the `mac` channel commands.
+    pub id: u8,
+    /// The channel's centre frequency in Hz.
+    pub frequency: u32,
+    /// The lowest data rate index allowed on the channel.
+    pub min_data_rate: u8,
+    /// The highest data rate index allowed on the channel.
+    pub max_data_rate: u8,
+}
+
+/// The modulation a data rate index stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataRate {
+    /// LoRa with the given spreading factor and bandwidth.
+    Lora(SpreadingFactor, Bandwidth),
+    /// FSK at the given bit rate, in bits per second.
+    Fsk(u32),
+}
+
+/// A sub-band whose transmissions are limited to a share of the time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubBand {
+    /// The frequencies in the sub-band, in Hz.
+    pub frequencies: RangeInclusive<u32>,
+    /// The largest share of time a device may transmit in the sub-band, in tenths of a
+    /// percent: 10 is a 1% duty cycle.
+    pub duty_cycle_permille: u16,
+}
+
+/// The data rates of the European plans, DR0 to DR7.
+const EU_DATA_RATES: &[DataRate] = &[
+    DataRate::Lora(SpreadingFactor::Sf12, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf11, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf10, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf9, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf7, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf7, Bandwidth::Khz250),
+    DataRate::Fsk(50_000),
+];
+
+/// The uplink data rates of the US915 plan, DR0 to DR4.
+const US915_DATA_RATES: &[DataRate] = &[
+    DataRate::Lora(SpreadingFactor::Sf10, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf9, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf7, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz500),
+];
+
+/// The uplink data rates of the AU915 plan, DR0 to DR6.
+const AU915_DATA_RATES: &[DataRate] = &[
+    DataRate::Lora(SpreadingFactor::Sf12, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf11, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf10, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf9, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf7, Bandwidth::Khz125),
+    DataRate::Lora(SpreadingFactor::Sf8, Bandwidth::Khz500),
+];
+
+/// The sub-bands of the 868 MHz band, per ETSI EN 300 220.
+const EU868_SUB_BANDS: &[SubBand] = &[
+    SubBand {
+        frequencies: 863_000_000..=865_000_000,
+        duty_cycle_permille: 1,
+    },
+    SubBand {
+        frequencies: 865_000_000..=868_000_000,
+        duty_cycle_permille: 10,
+    },
+    SubBand {
+        frequencies: 868_000_000..=868_600_000,
+        duty_cycle_permille: 10,
+    },
+    SubBand {
+        frequencies: 868_700_000..=869_200_000,
+        duty_cycle_permille: 1,
+    },
+    SubBand {
+        frequencies: 869_400_000..=869_650_000,
+        duty_cycle_permille: 100,
+    },
+    SubBand {
+        frequencies: 869_700_000..=870_000_000,
+        duty_cycle_permille: 10,
+    },
+];
+
+/// The 433 MHz band, which is a single sub-band.
+const EU433_SUB_BANDS: &[SubBand] = &[SubBand {
+    frequencies: 433_050_000..=434_790_000,
+    duty_cycle_permille: 100,
+}];
+
+impl Region {
+    /// Every region, in the order `Model::regions()` lists them.
+    pub const ALL: [Region; 4] = [Region::Eu868, Region::Eu433, Region::Us915, Region::Au915];
+
+    /// Returns the model of module built for the region.
+    pub fn model(self) -> Model {
+        match self {
+            Region::Eu868 | Region::Eu433 => Model::Rn2483,
+            Region::Us915 | Region::Au915 => Model::Rn2903,
+        }
+    }
+
+    /// Returns the frequencies, in Hz, a device may transmit on in the region.
+    pub fn frequencies(self) -> RangeInclusive<u32> {
+        match self {
+            Region::Eu868 => 863_000_000..=870_000_000,
+            Region::Eu433 => 433_050_000..=434_790_000,
+            Region::Us915 => 902_000_000..=928_000_000,
+            Region::Au915 => 915_000_000..=928_000_000,
+        }
+    }
+
+    /// Returns whether the given frequency, in Hz, is in the region's band.
+    pub fn contains(self, hz: u32) -> bool {
+        self.frequencies().contains(&hz)
+    }
+
+    /// Returns the maximum EIRP, in dBm, the region allows.
+    pub fn max_eirp(self) -> i8 {
+        match self {
+            Region::Eu868 => 16,
+            Region::Eu433 => 12,
+            Region::Us915 | Region::Au915 => 30,
+        }
+    }
+
+    /// Returns the number of channels the module's LoRaWAN stack keeps in the region:
+    /// 16 for the European plans and 72 for the others.
+    pub fn channel_count(self) -> u8 {
+        match self {
+            Region::Eu868 | Region::Eu433 => 16,
+            Region::Us915 | Region::Au915 => 72,
+        }
+    }
+
+    /// Returns the channel the plan fixes at the given index.
+    ///
+    /// The European plans fix only channels 0 to 2; the others are defined by the
+    /// application or the network, so `None` is returned for them as well as for
+    /// indices beyond `::channel_count()`. The other plans fix 64 channels of 125 kHz
+    /// followed by 8 of 500 kHz.
+    pub fn channel(self, id: u8) -> Option<Channel> {
+        let (frequency, min_data_rate, max_data_rate) = match (self, id) {
+            (Region::Eu868, 0..=2) => (868_100_000 + u32::from(id) * 200_000, 0, 5),
+            (Region::Eu433, 0..=2) => (433_175_000 + u32::from(id) * 200_000, 0, 5),
+            (Region::Us915, 0..=63) => (902_300_000 + u32::from(id) * 200_000, 0, 3),
+            (Region::Us915, 64..=71) => (903_000_000 + u32::from(id - 64) * 1_600_000, 4, 4),
+            (Region::Au915, 0..=63) => (915_200_000 + u32::from(id) * 200_000, 0, 5),
+            (Region::Au915, 64..=71) => (915_900_000 + u32::from(id - 64) * 1_600_000, 6, 6),
+            _ => return None,
+        };
+        Some(Channel {
+            id,
+            frequency,
+            min_data_rate,
+            max_data_rate,
+        })
+    }
+
+    /// Returns every channel the plan fixes, in order.
+    pub fn channels(self) -> impl Iterator<Item = Channel> {
+        (0..self.channel_count()).filter_map(move |id| self.channel(id))
+    }
+
+    /// Returns the modulation of the given uplink data rate index, or `None` if the
+    /// region does not define it.
+    pub fn data_rate(self, index: u8) -> Option<DataRate> {
+        let rates = match self {
+            Region::Eu868 | Region::Eu433 => EU_DATA_RATES,
+            Region::Us915 => US915_DATA_RATES,
+            Region::Au915 => AU915_DATA_RATES,
+        };
+        rates.get(usize::from(index)).copied()
+    }
+
+    /// Returns the sub-bands whose duty cycle is limited. The 915 MHz plans limit dwell
+    /// time instead, so have none.
+    pub fn sub_bands(self) -> &'static [SubBand] {
+        match self {
+            Region::Eu868 => EU868_SUB_BANDS,
+            Region::Eu433 => EU433_SUB_BANDS,
+            Region::Us915 | Region::Au915 => &[],
+        }
+    }
+
+    /// Returns the duty-cycle sub-band containing the given frequency, in Hz.
+    pub fn sub_band(self, hz: u32) -> Option<&'static SubBand> {
+        self.sub_bands()
+            .iter()
+            .find(|band| band.frequencies.contains(&hz))
+    }
+}
+
+/// # Regions
+impl Model {
+    /// Returns the regions the module is built for.
+    pub fn regions(self) -> &'static [Region] {
+        match self {
+            Model::Rn2483 => &Region::ALL[..2],
+            Model::Rn2903 => &Region::ALL[2..],
+        }
+    }
+
+    /// Returns whether the module's radio can be tuned to the given frequency, in Hz.
+    pub fn supports_frequency(self, hz: u32) -> bool {
+        self.regions().iter().any(|region| region.contains(hz))
+    }
+
+    /// Returns the number of channels the module's LoRaWAN stack keeps: 16 for the
+    /// RN2483 and 72 for the RN2903.
+    pub fn channel_count(self) -> u8 {
+        self.regions()[0].channel_count()
+    }
+
+    /// Returns the output powers, in dBm, the module accepts: -3 to 15 for the RN2483
+    /// and 2 to 20 for the RN2903.
+    pub fn power_range(self) -> RangeInclusive<i8> {
+        match self {
+            Model::Rn2483 => -3..=15,
+            Model::Rn2903 => 2..=20,
+        }
+    }
+}
+
+/// Checks the frequency, in Hz, is in a band of the given model, or of either model if
+/// it is not known, failing with `Error::OutOfRange` for the given parameter otherwise.
+pub(crate) fn check_frequency(
+    model: Option<Model>,
+    parameter: &'static str,
+    hz: u32,
+) -> Result<()> {
+    let supported = match model {
+        Some(model) => model.supports_frequency(hz),
+        None => Region::ALL.iter().any(|region| region.contains(hz)),
+    };
+    if supported {
+        Ok(())
+    } else {
+        Err(Error::out_of_range(parameter, hz))
+    }
+}
+
+/// Checks the channel index is below the channel count of the given model, or of the
+/// RN2903 if it is not known, failing with `Error::OutOfRange` otherwise.
+pub(crate) fn check_channel(model: Option<Model>, channel: u8) -> Result<()> {
+    let count = model.map_or(Model::Rn2903.channel_count(), Model::channel_count);
+    if channel < count {
+        Ok(())
+    } else {
+        Err(Error::out_of_range("channel", channel))
+    }
+}
+
+/// Returns every channel of the 915 MHz plans along with whether it belongs to the given
+/// sub-band, numbered from 1: the eight 125 kHz channels from `8 * (n - 1)` and the
+/// 500 kHz channel `63 + n`. Fails with `Error::OutOfRange` for sub-bands other than 1 to
+/// 8, and for every sub-band on the RN2483, whose plans have none.
+pub(crate) fn subband_channels(
+    model: Option<Model>,
+    subband: u8,
+) -> Result<impl Iterator<Item = (u8, bool)> + Clone> {
+    if model == Some(Model::Rn2483) || !(1..=8).contains(&subband) {
+        return Err(Error::out_of_range("sub-band", subband));
+    }
+    Ok((0..Model::Rn2903.channel_count()).map(move |channel| {
+        let group = if channel < 64 {
+            channel / 8
+        } else {
+            channel - 64
+        };
+        (channel, group + 1 == subband)
+    }))
+}
+
+/// Checks the output power, in dBm, is accepted by the given model, or by either model if
+/// it is not known, failing with `Error::OutOfRange` otherwise.
+pub(crate) fn check_power(model: Option<Model>, dbm: i8) -> Result<()> {
+    let range = model.map_or(POWER_RANGE, Model::power_range);
+    if range.contains(&dbm) {
+        Ok(())
+    } else {
+        Err(Error::out_of_range("radio power", dbm))
+    }
+}
@@ -0,0 +1,872 @@
+//! An in-process simulation of RN2903 firmware, for exercising code without hardware.
+//!
+//! The [`Simulator`](struct.Simulator.html) implements [`Transport`](../trait.Transport.html),
+//! so it can be handed to [`Rn2903::new()`](../struct.Rn2903.html#method.new) in place of
+//! a serial port. It parses the same ASCII command lines the real module does and
+//! answers with the same replies, keeping track of the state a real module would: the
+//! user NVM area, the pin modes and levels, the radio settings, the LoRaWAN identifiers,
+//! keys, and channels, and whether the LoRaWAN MAC is paused or joined.
+//!
+//! # Examples
+//!
+//! Receiving a packet which was queued on the simulated radio.
+//!
+//! ```
+//! # use rn2903::Rn2903;
+//! use rn2903::sim::Simulator;
+//!
+//! let mut sim = Simulator::new();
+//! sim.queue_packet(b"hello");
+//! let mut txvr = Rn2903::new(sim).unwrap();
+//! txvr.mac_pause().unwrap();
+//! assert_eq!(txvr.radio_rx(65535).unwrap(), Some(b"hello".to_vec()));
+//! assert_eq!(txvr.radio_rx(65535).unwrap(), None);
+//! ```
+
+use crate::mac::Downlink;
+use crate::protocol::{Event, Response};
+use crate::radio::{on_off, parse_on_off};
+use crate::region::Region;
+use crate::{FirmwareVersion, Model, Pin, PinMode, Transport};
+use core::fmt;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+/// The version string reported by a simulated RN2903.
+pub const RN2903_VERSION: &str = "RN2903 1.0.5 Nov 06 2018 10:45:27";
+
+/// The version string reported by a simulated RN2483.
+pub const RN2483_VERSION: &str = "RN2483 1.0.5 Oct 31 2018 15:06:52";
+
+/// The EUI-64 preprogrammed into the simulated module, with Microchip's OUI.
+const HWEUI: &str = "0004A30B00F1E2D3";
+
+/// The supply voltage, in millivolts, reported until changed with `set_vdd`.
+const DEFAULT_VDD: u16 = 3_300;
+
+/// The number of milliseconds reported by `mac pause` when the MAC is idle.
+const MAC_PAUSE_MAX: u32 = 4_294_967_245;
+
+/// First address of the user-accessible NVM area.
+const NVM_START: u16 = 0x300;
+
+/// Last address of the user-accessible NVM area.
+const NVM_END: u16 = 0x3FF;
+
+/// The signal-to-noise ratio, in dB, of packets queued without one.
+const DEFAULT_SNR: i8 = 9;
+
+/// The signal strength, in dBm, of packets queued without one.
+const DEFAULT_RSSI: i16 = -47;
+
+/// The byte the module measures the baud rate from after a break.
+const AUTOBAUD_BYTE: u8 = 0x55;
+
+/// The lowest 10-bit input value read as high by a digital input.
+const DIGITAL_HIGH_THRESHOLD: u16 = 512;
+
+/// The values of every `radio` parameter after a reset, as documented in the command
+/// reference.
+const RADIO_DEFAULTS: &[(&str, &str)] = &[
+    ("mod", "lora"),
+    ("freq", "923300000"),
+    ("pwr", "2"),
+    ("sf", "sf12"),
+    ("afcbw", "41.7"),
+    ("rxbw", "25"),
+    ("bitrate", "50000"),
+    ("fdev", "25000"),
+    ("prlen", "8"),
+    ("crc", "on"),
+    ("iqi", "off"),
+    ("cr", "4/5"),
+    ("wdt", "15000"),
+    ("sync", "34"),
+    ("bw", "125"),
+    ("bt", "0.5"),
+];
+
+/// The `mac` parameters which can be set, with the number of hexadecimal digits each
+/// takes.
+const MAC_PARAMETERS: &[(&str, usize)] = &[
+    ("deveui", 16),
+    ("appeui", 16),
+    ("appkey", 32),
+    ("devaddr", 8),
+    ("nwkskey", 32),
+    ("appskey", 32),
+];
+
+/// The duty cycle the RN2483 gives its default channels, 0.33%.
+const DEFAULT_DUTY_CYCLE: u16 = 302;
+
+/// The device address assigned by the simulated network on an over-the-air join.
+const OTAA_DEVADDR: &str = "260113D7";
+
+/// An uplink sent through the simulated LoRaWAN MAC with `mac tx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uplink {
+    /// Whether the uplink was sent as a confirmed message.
+    pub confirmed: bool,
+    /// The LoRaWAN port the uplink was sent to.
+    pub port: u8,
+    /// The application payload.
+    pub payload: Vec<u8>,
+}
+
+/// The settings of a LoRaWAN MAC channel.
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    frequency: u32,
+    duty_cycle: u16,
+    data_rates: (u8, u8),
+    enabled: bool,
+}
+
+/// A simulated RN2903 (or RN2483) module.
+///
+/// Bytes written to the simulator are treated as commands; once a complete CRLF-terminated
+/// line has been written, the replies become available to `read`. Like the real module,
+/// the second reply to commands like `radio rx` only arrives after the first has been
+/// read. If no reply is pending, `read` fails with `TimedOut`, just like a serial port
+/// whose timeout has elapsed.
+///
+/// # Examples
+///
+/// Checking what application code wrote to the user NVM area.
+///
+/// ```
+/// # use rn2903::{NvmAddress, Rn2903};
+/// use rn2903::sim::Simulator;
+///
+/// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+/// txvr.system_set_nvm(NvmAddress::new(0x3A0), 0x42).unwrap();
+/// assert_eq!(txvr.system_get_nvm(NvmAddress::new(0x3A0)).unwrap(), 0x42);
+/// assert_eq!(txvr.port().nvm(0x3A0), Some(0x42));
+/// assert_eq!(txvr.port().commands().last().unwrap(), "sys get nvm 3a0");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    version: String,
+    input: Vec<u8>,
+    output: VecDeque<u8>,
+    deferred: VecDeque<String>,
+    nvm: [u8; 256],
+    pin_modes: BTreeMap<Pin, PinMode>,
+    pin_outputs: BTreeMap<Pin, bool>,
+    pin_inputs: BTreeMap<Pin, u16>,
+    asleep_until: Option<Instant>,
+    awaiting_autobaud: bool,
+    vdd: u16,
+    radio: BTreeMap<&'static str, String>,
+    mac: BTreeMap<&'static str, String>,
+    channels: Vec<ChannelState>,
+    mac_paused: bool,
+    joined: bool,
+    deny_joins: bool,
+    uplinks: Vec<Uplink>,
+    downlinks: VecDeque<(u8, Vec<u8>)>,
+    received: VecDeque<(Vec<u8>, i8, i16)>,
+    last_quality: (i8, i16),
+    transmitted: Vec<Vec<u8>>,
+    commands: Vec<String>,
+    reader: Option<Waker>,
+    #[cfg(feature = "async")]
+    wake_scheduled: Option<Instant>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// Creates a simulated RN2903 with factory-fresh state.
+    pub fn new() -> Self {
+        Self::with_version(RN2903_VERSION)
+    }
+
+    /// Creates a simulated RN2483 with factory-fresh state.
+    pub fn rn2483() -> Self {
+        Self::with_version(RN2483_VERSION)
+    }
+
+    /// Creates a simulated module with factory-fresh state which reports the given
+    /// string in response to `sys get ver`.
+    pub fn with_version<S: Into<String>>(version: S) -> Self {
+        let mut new = Self {
+            version: version.into(),
+            input: Vec::new(),
+            output: VecDeque::new(),
+            deferred: VecDeque::new(),
+            nvm: [0xFF; 256],
+            pin_modes: BTreeMap::new(),
+            pin_outputs: BTreeMap::new(),
+            pin_inputs: BTreeMap::new(),
+            asleep_until: None,
+            awaiting_autobaud: false,
+            vdd: DEFAULT_VDD,
+            radio: BTreeMap::new(),
+            mac: BTreeMap::new(),
+            channels: Vec::new(),
+            mac_paused: false,
+            joined: false,
+            deny_joins: false,
+            uplinks: Vec::new(),
+            downlinks: VecDeque::new(),
+            received: VecDeque::new(),
+            last_quality: (DEFAULT_SNR, DEFAULT_RSSI),
+            transmitted: Vec::new(),
+            commands: Vec::new(),
+            reader: None,
+            #[cfg(feature = "async")]
+            wake_scheduled: None,
+        };
+        new.reset();
+        new
+    }
+
+    /// Queues a packet to be delivered by the next `radio rx` command. If no packet is
+    /// queued, `radio rx` reports a watchdog timeout with `radio_err`.
+    pub fn queue_packet(&mut self, payload: &[u8]) {
+        self.queue_packet_with_quality(payload, DEFAULT_SNR, DEFAULT_RSSI);
+    }
+
+    /// Queues a packet as `queue_packet` does, which `radio get snr` and
+    /// `radio get pktrssi` report as having been received with the given signal-to-noise
+    /// ratio in dB and signal strength in dBm.
+    pub fn queue_packet_with_quality(&mut self, payload: &[u8], snr: i8, rssi: i16) {
+        self.received.push_back((payload.to_vec(), snr, rssi));
+    }
+
+    /// Returns the payloads sent with `radio tx` so far, oldest first.
+    pub fn transmitted(&self) -> &[Vec<u8>] {
+        &self.transmitted
+    }
+
+    /// Returns every command line received so far, oldest first, without CRLFs.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// Returns the value stored at the given user NVM address, or `None` if the address
+    /// lies outside 0x300 to 0x3FF.
+    pub fn nvm(&self, address: u16) -> Option<u8> {
+        if (NVM_START..=NVM_END).contains(&address) {
+            Some(self.nvm[(address - NVM_START) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the current value of the named `radio` parameter, as `radio get` would
+    /// report it.
+    pub fn radio_parameter(&self, name: &str) -> Option<&str> {
+        self.radio.get(name).map(String::as_str)
+    }
+
+    /// Returns whether the LoRaWAN MAC is currently paused.
+    pub fn mac_paused(&self) -> bool {
+        self.mac_paused
+    }
+
+    /// Returns the uplinks sent with `mac tx` so far, oldest first.
+    pub fn uplinks(&self) -> &[Uplink] {
+        &self.uplinks
+    }
+
+    /// Queues a downlink to the given port, to be delivered in reply to the next
+    /// successful `mac tx` command.
+    pub fn queue_downlink(&mut self, port: u8, payload: &[u8]) {
+        self.downlinks.push_back((port, payload.to_vec()));
+    }
+
+    /// Returns whether the simulated module has joined a LoRaWAN network.
+    pub fn joined(&self) -> bool {
+        self.joined
+    }
+
+    /// Sets whether the simulated network denies join requests. By default, every join
+    /// request made with the required keys set is accepted.
+    pub fn deny_joins(&mut self, deny: bool) {
+        self.deny_joins = deny;
+    }
+
+    /// Applies a voltage to the given pin, as the 10-bit value the module's converter
+    /// reads it as. Digital inputs read values from 512 up as high. Every pin reads 0
+    /// until set.
+    pub fn set_pin_input(&mut self, pin: Pin, value: u16) {
+        self.pin_inputs.insert(pin, value.min(1023));
+    }
+
+    /// Returns the level the given pin is driven to, or `None` if it is not configured as
+    /// a digital output.
+    pub fn pin_output(&self, pin: Pin) -> Option<bool> {
+        match self.pin_mode(pin) {
+            PinMode::DigitalOutput => Some(self.pin_outputs.get(&pin) == Some(&true)),
+            _ => None,
+        }
+    }
+
+    /// Returns the mode the given pin is configured for.
+    pub fn pin_mode(&self, pin: Pin) -> PinMode {
+        self.pin_modes
+            .get(&pin)
+            .copied()
+            .unwrap_or(PinMode::DigitalOutput)
+    }
+
+    /// Sets the supply voltage, in millivolts, reported by `sys get vdd`. It is 3300 mV
+    /// by default.
+    pub fn set_vdd(&mut self, millivolts: u16) {
+        self.vdd = millivolts;
+    }
+
+    /// Returns whether the simulated module is asleep after `sys sleep`.
+    pub fn asleep(&self) -> bool {
+        self.asleep_until.is_some()
+    }
+
+    /// Wakes the module, reporting so with `ok`, once its sleep has run out.
+    fn wake_if_due(&mut self) {
+        if let Some(until) = self.asleep_until {
+            if Instant::now() >= until {
+                self.asleep_until = None;
+                self.reply(Response::Ok);
+            }
+        }
+    }
+
+    /// Restores the state which is lost when the module is reset.
+    fn reset(&mut self) {
+        self.pin_modes.clear();
+        self.pin_outputs.clear();
+        self.radio = RADIO_DEFAULTS
+            .iter()
+            .map(|&(name, value)| (name, value.to_string()))
+            .collect();
+        if self.model() == Some(Model::Rn2483) {
+            self.radio.insert("freq", "868100000".to_string());
+            self.radio.insert("pwr", "1".to_string());
+        }
+        self.mac.clear();
+        let region = self.region();
+        self.channels = (0..region.channel_count())
+            .map(|id| match region.channel(id) {
+                Some(channel) => ChannelState {
+                    frequency: channel.frequency,
+                    duty_cycle: DEFAULT_DUTY_CYCLE,
+                    data_rates: (channel.min_data_rate, channel.max_data_rate),
+                    enabled: true,
+                },
+                None => ChannelState {
+                    frequency: 0,
+                    duty_cycle: 0,
+                    data_rates: (0, 0),
+                    enabled: false,
+                },
+            })
+            .collect();
+        self.mac_paused = false;
+        self.joined = false;
+    }
+
+    /// Returns the model named by the version string, if it names one.
+    fn model(&self) -> Option<Model> {
+        FirmwareVersion::parse(&self.version).map(|version| version.model)
+    }
+
+    /// Returns the region whose channel plan the module starts with: EU868 for an RN2483
+    /// and US915 otherwise.
+    fn region(&self) -> Region {
+        self.model()
+            .map_or(Region::Us915, |model| model.regions()[0])
+    }
+
+    /// Queues a single reply line to be read by the host.
+    fn reply<D: fmt::Display>(&mut self, line: D) {
+        self.output.extend(line.to_string().bytes());
+        self.output.extend(b"\r\n");
+        self.wake_reader();
+    }
+
+    /// Queues a reply line which only becomes readable once every earlier reply has been
+    /// read, like the asynchronous second reply of `radio rx` or `radio tx`.
+    fn reply_later<D: fmt::Display>(&mut self, line: D) {
+        self.deferred.push_back(line.to_string());
+        self.wake_reader();
+    }
+
+    /// Wakes the task waiting to read, if there is one, as a line has been queued.
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.reader.take() {
+            waker.wake();
+        }
+    }
+
+    /// Executes a complete command line, queueing the replies.
+    fn execute(&mut self, line: &str) {
+        self.commands.push(line.to_string());
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["sys", rest @ ..] => self.execute_sys(rest),
+            ["mac", rest @ ..] => self.execute_mac(rest),
+            ["radio", rest @ ..] => self.execute_radio(rest),
+            _ => self.reply(Response::InvalidParam),
+        }
+    }
+
+    fn execute_sys(&mut self, words: &[&str]) {
+        match words {
+            ["get", "ver"] => {
+                let version = self.version.clone();
+                self.reply(version);
+            }
+            ["reset"] => {
+                self.reset();
+                let version = self.version.clone();
+                self.reply(version);
+            }
+            ["factoryRESET"] => {
+                self.reset();
+                self.nvm = [0xFF; 256];
+                let version = self.version.clone();
+                self.reply(version);
+            }
+            ["get", "nvm", address] => match parse_nvm_address(address) {
+                Some(index) => {
+                    let value = self.nvm[index];
+                    self.reply(format!("{:02X}", value));
+                }
+                None => self.reply(Response::InvalidParam),
+            },
+            ["set", "nvm", address, value] => {
+                match (parse_nvm_address(address), u8::from_str_radix(value, 16)) {
+                    (Some(index), Ok(value)) => {
+                        self.nvm[index] = value;
+                        self.reply(Response::Ok);
+                    }
+                    _ => self.reply(Response::InvalidParam),
+                }
+            }
+            ["get", "vdd"] => {
+                let vdd = self.vdd;
+                self.reply(vdd);
+            }
+            ["get", "hweui"] => self.reply(HWEUI),
+            ["sleep", ms] => match ms.parse::<u32>() {
+                Ok(ms) if ms >= 100 => {
+                    let duration = Duration::from_millis(ms.into());
+                    self.asleep_until = Some(Instant::now() + duration);
+                }
+                _ => self.reply(Response::InvalidParam),
+            },
+            ["set", "pinmode", pin, mode] => {
+                match (Pin::from_token(pin), PinMode::from_token(mode)) {
+                    (Some(pin), Some(mode)) if mode != PinMode::Analog || pin.supports_analog() => {
+                        self.pin_modes.insert(pin, mode);
+                        self.reply(Response::Ok);
+                    }
+                    _ => self.reply(Response::InvalidParam),
+                }
+            }
+            ["set", "pindig", pin, level] => match (Pin::from_token(pin), *level) {
+                (Some(pin), "0") | (Some(pin), "1")
+                    if self.pin_mode(pin) == PinMode::DigitalOutput =>
+                {
+                    self.pin_outputs.insert(pin, *level == "1");
+                    self.reply(Response::Ok);
+                }
+                _ => self.reply(Response::InvalidParam),
+            },
+            ["get", "pindig", pin] => match Pin::from_token(pin) {
+                Some(pin) => match self.pin_mode(pin) {
+                    PinMode::DigitalOutput => {
+                        let high = self.pin_output(pin) == Some(true);
+                        self.reply(high as u8);
+                    }
+                    PinMode::DigitalInput => {
+                        let high = self.pin_input(pin) >= DIGITAL_HIGH_THRESHOLD;
+                        self.reply(high as u8);
+                    }
+                    PinMode::Analog => self.reply(Response::InvalidParam),
+                },
+                None => self.reply(Response::InvalidParam),
+            },
+            ["get", "pinana", pin] => match Pin::from_token(pin) {
+                Some(pin) if self.pin_mode(pin) == PinMode::Analog => {
+                    let value = self.pin_input(pin);
+                    self.reply(value);
+                }
+                _ => self.reply(Response::InvalidParam),
+            },
+            _ => self.reply(Response::InvalidParam),
+        }
+    }
+
+    /// Returns the 10-bit value applied to the given pin.
+    fn pin_input(&self, pin: Pin) -> u16 {
+        self.pin_inputs.get(&pin).copied().unwrap_or(0)
+    }
+
+    fn execute_mac(&mut self, words: &[&str]) {
+        match words {
+            ["pause"] => {
+                self.mac_paused = true;
+                self.reply(MAC_PAUSE_MAX.to_string());
+            }
+            ["resume"] => {
+                self.mac_paused = false;
+                self.reply(Response::Ok);
+            }
+            ["set", name, value] => match MAC_PARAMETERS.iter().find(|(n, _)| n == name) {
+                Some(&(name, digits)) if value.len() == digits && decode_hex(value).is_some() => {
+                    self.mac.insert(name, value.to_uppercase());
+                    self.reply(Response::Ok);
+                }
+                _ => self.reply(Response::InvalidParam),
+            },
+            ["get", name @ ("deveui" | "appeui" | "devaddr")] => {
+                let digits = if *name == "devaddr" { 8 } else { 16 };
+                let value = self
+                    .mac
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| "0".repeat(digits));
+                self.reply(value);
+            }
+            ["set", "ch", name, id, values @ ..] => self.set_channel(name, id, values),
+            ["get", "ch", name, id] => self.get_channel(name, id),
+            ["join", mode @ ("otaa" | "abp")] => {
+                let required: &[&str] = if *mode == "otaa" {
+                    &["deveui", "appeui", "appkey"]
+                } else {
+                    &["devaddr", "nwkskey", "appskey"]
+                };
+                if self.mac_paused {
+                    return self.reply(Response::MacPaused);
+                }
+                if !required.iter().all(|k| self.mac.contains_key(k)) {
+                    return self.reply(Response::KeysNotInit);
+                }
+                self.reply(Response::Ok);
+                if self.deny_joins {
+                    self.joined = false;
+                    self.reply_later(Event::Denied);
+                } else {
+                    if *mode == "otaa" {
+                        self.mac.insert("devaddr", OTAA_DEVADDR.to_string());
+                    }
+                    self.joined = true;
+                    self.reply_later(Event::Accepted);
+                }
+            }
+            ["tx", kind @ ("cnf" | "uncnf"), port, data] => {
+                let port = match port.parse::<u8>() {
+                    Ok(p) if (1..=223).contains(&p) => p,
+                    _ => return self.reply(Response::InvalidParam),
+                };
+                let payload = match decode_hex(data) {
+                    Some(payload) => payload,
+                    None => return self.reply(Response::InvalidParam),
+                };
+                if self.mac_paused {
+                    return self.reply(Response::MacPaused);
+                }
+                if !self.joined {
+                    return self.reply(Response::NotJoined);
+                }
+                self.uplinks.push(Uplink {
+                    confirmed: *kind == "cnf",
+                    port,
+                    payload,
+                });
+                self.reply(Response::Ok);
+                match self.downlinks.pop_front() {
+                    Some((port, payload)) => {
+                        self.reply_later(Event::MacRx(Downlink { port, payload }))
+                    }
+                    None => self.reply_later(Event::MacTxOk),
+                }
+            }
+            _ => self.reply(Response::InvalidParam),
+        }
+    }
+
+    /// Sets a channel parameter with `mac set ch`. Only the RN2483 lets the frequency and
+    /// duty cycle be set, and only channels 3 and up have a frequency which can be.
+    fn set_channel(&mut self, name: &str, id: &str, values: &[&str]) {
+        let region = self.region();
+        let eu = region.model() == Model::Rn2483;
+        let id = match id.parse::<u8>() {
+            Ok(id) if id < region.channel_count() => id,
+            _ => return self.reply(Response::InvalidParam),
+        };
+        let channel = &mut self.channels[usize::from(id)];
+        let valid = match (name, values) {
+            ("freq", [hz]) if eu && id >= 3 => match hz.parse() {
+                Ok(hz) if region.contains(hz) => {
+                    channel.frequency = hz;
+                    true
+                }
+                _ => false,
+            },
+            ("dcycle", [value]) if eu => match value.parse() {
+                Ok(value) => {
+                    channel.duty_cycle = value;
+                    true
+                }
+                Err(_) => false,
+            },
+            ("drrange", [min, max]) => match (min.parse(), max.parse()) {
+                (Ok(min), Ok(max)) if min <= max && region.data_rate(max).is_some() => {
+                    channel.data_rates = (min, max);
+                    true
+                }
+                _ => false,
+            },
+            ("status", [status]) => match parse_on_off(status) {
+                Some(enabled) => {
+                    channel.enabled = enabled;
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        };
+        self.reply(if valid {
+            Response::Ok
+        } else {
+            Response::InvalidParam
+        });
+    }
+
+    /// Queries a channel parameter with `mac get ch`.
+    fn get_channel(&mut self, name: &str, id: &str) {
+        let eu = self.region().model() == Model::Rn2483;
+        let channel = match id
+            .parse::<usize>()
+            .ok()
+            .and_then(|id| self.channels.get(id))
+        {
+            Some(channel) => *channel,
+            None => return self.reply(Response::InvalidParam),
+        };
+        match name {
+            "freq" => self.reply(channel.frequency),
+            "dcycle" if eu => self.reply(channel.duty_cycle),
+            "drrange" => self.reply(format!("{} {}", channel.data_rates.0, channel.data_rates.1)),
+            "status" => self.reply(on_off(channel.enabled)),
+            _ => self.reply(Response::InvalidParam),
+        }
+    }
+
+    fn execute_radio(&mut self, words: &[&str]) {
+        match words {
+            ["get", "snr"] => {
+                let snr = self.last_quality.0;
+                self.reply(snr.to_string());
+            }
+            ["get", "pktrssi"] => {
+                let rssi = self.last_quality.1;
+                self.reply(rssi.to_string());
+            }
+            ["get", name] => match self.radio.get(name) {
+                Some(value) => {
+                    let value = value.clone();
+                    self.reply(value);
+                }
+                None => self.reply(Response::InvalidParam),
+            },
+            ["set", name, value] => match RADIO_DEFAULTS.iter().find(|(n, _)| n == name) {
+                Some(&(name, _)) if radio_value_is_valid(self.model(), name, value) => {
+                    self.radio.insert(name, value.to_string());
+                    self.reply(Response::Ok);
+                }
+                _ => self.reply(Response::InvalidParam),
+            },
+            ["rx", timeout] => {
+                if timeout.parse::<u16>().is_err() {
+                    return self.reply(Response::InvalidParam);
+                }
+                if !self.mac_paused {
+                    return self.reply(Response::Busy);
+                }
+                self.reply(Response::Ok);
+                match self.received.pop_front() {
+                    Some((payload, snr, rssi)) => {
+                        self.last_quality = (snr, rssi);
+                        self.reply_later(Event::RadioRx(payload))
+                    }
+                    None => self.reply_later(Event::RadioErr),
+                }
+            }
+            ["tx", data] => {
+                let max_len = if self.radio["mod"] == "lora" { 255 } else { 64 };
+                let payload = match decode_hex(data) {
+                    Some(payload) if payload.len() <= max_len => payload,
+                    _ => return self.reply(Response::InvalidParam),
+                };
+                if !self.mac_paused {
+                    return self.reply(Response::Busy);
+                }
+                self.transmitted.push(payload);
+                self.reply(Response::Ok);
+                self.reply_later(Event::RadioTxOk);
+            }
+            ["rxstop"] => self.reply(Response::Ok),
+            _ => self.reply(Response::InvalidParam),
+        }
+    }
+}
+
+impl Read for Simulator {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.wake_if_due();
+        if self.output.is_empty() {
+            if let Some(line) = self.deferred.pop_front() {
+                self.reply(line);
+            }
+        }
+        if self.output.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "simulated module has nothing to send",
+            ));
+        }
+        let mut count = 0;
+        while count < buf.len() {
+            match self.output.pop_front() {
+                Some(byte) => {
+                    buf[count] = byte;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Write for Simulator {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.wake_if_due();
+        let mut bytes = buf;
+        if self.awaiting_autobaud && !bytes.is_empty() {
+            self.awaiting_autobaud = false;
+            if bytes[0] == AUTOBAUD_BYTE {
+                bytes = &bytes[1..];
+                if self.asleep_until.take().is_some() {
+                    self.reply(Response::Ok);
+                }
+            }
+        }
+        if self.asleep() {
+            // The sleeping module ignores everything but a break and the autobaud byte.
+            return Ok(buf.len());
+        }
+        self.input.extend_from_slice(bytes);
+        while let Some(end) = self.input.windows(2).position(|w| w == b"\r\n") {
+            let line: Vec<u8> = self.input.drain(..end + 2).take(end).collect();
+            self.execute(&String::from_utf8_lossy(&line));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Simulator {
+    /// Has the simulated module take the next byte written as the 0x55 it measures the
+    /// baud rate from, waking it if it is asleep.
+    fn send_break(&mut self) -> io::Result<()> {
+        self.input.clear();
+        self.awaiting_autobaud = true;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl_async_in_memory!(Simulator);
+
+#[cfg(feature = "async")]
+impl crate::asynchronous::InMemory for Simulator {
+    /// Besides queued lines, the `ok` of a sleeping module falls due with nothing
+    /// written, so a thread is left to wake the reader when it does, once per sleep.
+    fn wake_when_readable(&mut self, waker: Waker) {
+        if let Some(until) = self.asleep_until {
+            if self.wake_scheduled != Some(until) {
+                self.wake_scheduled = Some(until);
+                let waker = waker.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(until.saturating_duration_since(Instant::now()));
+                    waker.wake();
+                });
+            }
+        }
+        self.reader = Some(waker);
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::AsyncBreak for Simulator {
+    fn send_break(&mut self) -> io::Result<()> {
+        Transport::send_break(self)
+    }
+}
+
+/// Parses a hexadecimal NVM address, returning its index into the user area.
+fn parse_nvm_address(address: &str) -> Option<usize> {
+    match u16::from_str_radix(address, 16) {
+        Ok(a) if (NVM_START..=NVM_END).contains(&a) => Some((a - NVM_START) as usize),
+        _ => None,
+    }
+}
+
+/// Checks a value given to `radio set` against the ranges in the command reference for
+/// the given model, or for either if the version string names neither.
+fn radio_value_is_valid(model: Option<Model>, name: &str, value: &str) -> bool {
+    fn in_range(value: &str, min: i64, max: i64) -> bool {
+        match value.parse::<i64>() {
+            Ok(v) => v >= min && v <= max,
+            Err(_) => false,
+        }
+    }
+    const FSK_BANDWIDTHS: &[&str] = &[
+        "250", "125", "62.5", "31.3", "15.6", "7.8", "3.9", "200", "100", "50", "25", "12.5",
+        "6.3", "3.1", "166.7", "83.3", "41.7", "20.8", "10.4", "5.2", "2.6",
+    ];
+    match name {
+        "mod" => value == "lora" || value == "fsk",
+        "freq" => matches!(value.parse(), Ok(hz) if match model {
+            Some(model) => model.supports_frequency(hz),
+            None => Region::ALL.iter().any(|region| region.contains(hz)),
+        }),
+        "pwr" => {
+            let range = model.map_or(-3..=20, Model::power_range);
+            matches!(value.parse(), Ok(dbm) if range.contains(&dbm))
+        }
+        "sf" => ["sf7", "sf8", "sf9", "sf10", "sf11", "sf12"].contains(&value),
+        "afcbw" | "rxbw" => FSK_BANDWIDTHS.contains(&value),
+        "bitrate" => in_range(value, 1, 300_000),
+        "fdev" => in_range(value, 0, 200_000),
+        "prlen" => in_range(value, 0, 65535),
+        "crc" | "iqi" => value == "on" || value == "off",
+        "cr" => ["4/5", "4/6", "4/7", "4/8"].contains(&value),
+        "wdt" => in_range(value, 0, 4_294_967_295),
+        "sync" => value.len() <= 16 && matches!(decode_hex(value), Some(b) if !b.is_empty()),
+        "bw" => ["125", "250", "500"].contains(&value),
+        "bt" => ["none", "1.0", "0.5", "0.3"].contains(&value),
+        _ => false,
+    }
+}
+
+/// Decodes hexadecimal of either case, as the module accepts.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex::decode(hex).ok()
+}
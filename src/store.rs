@@ -0,0 +1,348 @@
+//! A small versioned key-value store kept in the user area of nonvolatile memory.
+
+use crate::{Error, NvmAddress, NvmWriteOptions, Result, Rn2903, Transport, NVM_SIZE};
+use std::collections::BTreeMap;
+
+/// Marks the start of a store in nonvolatile memory.
+const MAGIC: [u8; 4] = *b"RNKV";
+
+/// The version of the layout below, which changes only if this crate changes it.
+const FORMAT_VERSION: u8 = 1;
+
+/// The size of the header: magic, format version, schema version, record area length
+/// (little-endian `u16`), and CRC (little-endian `u16`).
+const HEADER_LEN: usize = 10;
+
+/// The first address of the store.
+const BASE_ADDRESS: u16 = 0x300;
+
+/// A value which can be kept in an [`NvmStore`](struct.NvmStore.html).
+///
+/// Integers are stored little-endian, `bool` as a single 0 or 1 byte, and `String` and
+/// `Vec<u8>` as their bytes. Implement this for application types to store them
+/// directly.
+pub trait NvmRecord: Sized {
+    /// Encodes the value as the bytes to store.
+    fn to_nvm_bytes(&self) -> Vec<u8>;
+
+    /// Decodes a value from stored bytes, returning `None` if they do not hold one.
+    fn from_nvm_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_nvm_record_for_int {
+    ($($int:ty),*) => {$(
+        impl NvmRecord for $int {
+            fn to_nvm_bytes(&self) -> Vec<u8> {
+                self.to_le_bytes().to_vec()
+            }
+
+            fn from_nvm_bytes(bytes: &[u8]) -> Option<Self> {
+                let mut array = [0; core::mem::size_of::<$int>()];
+                if bytes.len() != array.len() {
+                    return None;
+                }
+                array.copy_from_slice(bytes);
+                Some(<$int>::from_le_bytes(array))
+            }
+        }
+    )*};
+}
+
+impl_nvm_record_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl NvmRecord for bool {
+    fn to_nvm_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+
+    fn from_nvm_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl NvmRecord for Vec<u8> {
+    fn to_nvm_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_nvm_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl NvmRecord for String {
+    fn to_nvm_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_nvm_bytes(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+/// Records keyed by a byte, kept in the user area of nonvolatile memory behind a header
+/// carrying an application-defined schema version and a CRC of the contents.
+///
+/// The store occupies the whole user area from 0x300, and holds up to
+/// [`::CAPACITY`](#associatedconstant.CAPACITY) bytes of records, each of which costs
+/// two bytes more than its value. It is edited in memory and only written back by
+/// [`::save()`](#method.save), which skips bytes that have not changed.
+///
+/// [`::load()`](#method.load) fails with `Error::NvmStoreUninitialized` if the module
+/// does not hold a store, such as a new module or one whose memory is used some other
+/// way, and with `Error::NvmStoreCorrupt` if the header or contents are damaged. The
+/// schema version is not checked, so applications can migrate records from older
+/// versions; see [`::schema()`](#method.schema).
+///
+/// For asynchronous use, [`::decode()`](#method.decode) and
+/// [`::encode()`](#method.encode) convert to and from the raw contents of the user
+/// area, to be read and written with `AsyncRn2903::{read_nvm_range, write_nvm_bytes}()`.
+///
+/// # Example
+///
+/// ```
+/// # use rn2903::{Error, NvmStore, Rn2903};
+/// # use rn2903::sim::Simulator;
+/// const SCHEMA: u8 = 1;
+/// const NODE_ID: u8 = 0;
+/// const CALIBRATION: u8 = 1;
+///
+/// let mut txvr = Rn2903::new(Simulator::new()).unwrap();
+/// let mut store = match NvmStore::load(&mut txvr) {
+///     Ok(store) if store.schema() == SCHEMA => store,
+///     Err(Error::NvmStoreUninitialized) => NvmStore::new(SCHEMA),
+///     other => panic!("unexpected {:?}", other),
+/// };
+/// store.put(NODE_ID, &42u16).unwrap();
+/// store.put(CALIBRATION, &-3i8).unwrap();
+/// store.save(&mut txvr).unwrap();
+///
+/// let store = NvmStore::load(&mut txvr).unwrap();
+/// assert_eq!(store.get::<u16>(NODE_ID), Some(42));
+/// assert_eq!(store.get::<i8>(CALIBRATION), Some(-3));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvmStore {
+    schema: u8,
+    records: BTreeMap<u8, Vec<u8>>,
+}
+
+impl NvmStore {
+    /// The number of bytes available for records, including the two bytes of key and
+    /// length taken by each.
+    pub const CAPACITY: usize = NVM_SIZE - HEADER_LEN;
+
+    /// Creates an empty store with the given schema version, to replace whatever the
+    /// module holds when saved.
+    pub fn new(schema: u8) -> Self {
+        NvmStore {
+            schema,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the store from the module's nonvolatile memory.
+    pub fn load<T: Transport>(txvr: &mut Rn2903<T>) -> Result<Self> {
+        let header = txvr.read_nvm_range(NvmAddress(BASE_ADDRESS), HEADER_LEN)?;
+        let len = record_area_len(&header)?;
+        let start = NvmAddress(BASE_ADDRESS + HEADER_LEN as u16);
+        let mut contents = header;
+        contents.extend(txvr.read_nvm_range(start, len)?);
+        Self::decode(&contents)
+    }
+
+    /// Writes the store to the module's nonvolatile memory, writing only the bytes which
+    /// differ from what the module holds and verifying each, and returns how many bytes
+    /// were written.
+    pub fn save<T: Transport>(&self, txvr: &mut Rn2903<T>) -> Result<usize> {
+        let options = NvmWriteOptions {
+            verify: true,
+            skip_unchanged: true,
+        };
+        txvr.write_nvm_bytes(NvmAddress(BASE_ADDRESS), &self.encode(), options)
+    }
+
+    /// Decodes a store from the contents of the user area, starting at 0x300. Bytes
+    /// beyond the end of the store are ignored.
+    ///
+    /// ```
+    /// # use rn2903::{Error, NvmStore, NVM_SIZE};
+    /// let mut store = NvmStore::new(3);
+    /// store.put(7, &"hello".to_string()).unwrap();
+    /// let mut contents = store.encode();
+    /// assert_eq!(NvmStore::decode(&contents).unwrap(), store);
+    ///
+    /// contents[12] ^= 0x01;
+    /// assert!(matches!(NvmStore::decode(&contents), Err(Error::NvmStoreCorrupt(_))));
+    /// assert!(matches!(
+    ///     NvmStore::decode(&[0xFF; NVM_SIZE]),
+    ///     Err(Error::NvmStoreUninitialized)
+    /// ));
+    /// ```
+    pub fn decode(contents: &[u8]) -> Result<Self> {
+        let len = record_area_len(contents)?;
+        let body = contents
+            .get(HEADER_LEN..HEADER_LEN + len)
+            .ok_or(Error::NvmStoreCorrupt("records are truncated"))?;
+        let crc = u16::from_le_bytes([contents[8], contents[9]]);
+        if crc16(&contents[4..8], body) != crc {
+            return Err(Error::NvmStoreCorrupt("CRC mismatch"));
+        }
+
+        let mut records = BTreeMap::new();
+        let mut rest = body;
+        while let [key, len, tail @ ..] = rest {
+            let len = usize::from(*len);
+            if tail.len() < len {
+                return Err(Error::NvmStoreCorrupt("a record is truncated"));
+            }
+            if records.insert(*key, tail[..len].to_vec()).is_some() {
+                return Err(Error::NvmStoreCorrupt("a key is repeated"));
+            }
+            rest = &tail[len..];
+        }
+        if !rest.is_empty() {
+            return Err(Error::NvmStoreCorrupt("a record is truncated"));
+        }
+
+        Ok(NvmStore {
+            schema: contents[5],
+            records,
+        })
+    }
+
+    /// Encodes the store as the bytes written to the user area, starting at 0x300.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.used());
+        for (key, value) in &self.records {
+            body.push(*key);
+            body.push(value.len() as u8);
+            body.extend_from_slice(value);
+        }
+        let mut contents = MAGIC.to_vec();
+        contents.push(FORMAT_VERSION);
+        contents.push(self.schema);
+        contents.extend_from_slice(&(body.len() as u16).to_le_bytes());
+        let crc = crc16(&contents[4..8], &body);
+        contents.extend_from_slice(&crc.to_le_bytes());
+        contents.extend(body);
+        contents
+    }
+
+    /// Returns the application-defined schema version the store was saved with.
+    pub fn schema(&self) -> u8 {
+        self.schema
+    }
+
+    /// Changes the schema version, such as after migrating records from an older one.
+    pub fn set_schema(&mut self, schema: u8) {
+        self.schema = schema;
+    }
+
+    /// Returns the record with the given key, or `None` if there is none or it does not
+    /// decode as the requested type.
+    pub fn get<R: NvmRecord>(&self, key: u8) -> Option<R> {
+        self.records
+            .get(&key)
+            .and_then(|bytes| R::from_nvm_bytes(bytes))
+    }
+
+    /// Returns the raw bytes of the record with the given key.
+    pub fn get_bytes(&self, key: u8) -> Option<&[u8]> {
+        self.records.get(&key).map(Vec::as_slice)
+    }
+
+    /// Adds or replaces the record with the given key.
+    ///
+    /// Fails with `Error::OutOfRange`, leaving the store unchanged, if the value is longer
+    /// than the 255 bytes a record can hold, or the record would not fit in the
+    /// remaining capacity.
+    pub fn put<R: NvmRecord>(&mut self, key: u8, value: &R) -> Result<()> {
+        let bytes = value.to_nvm_bytes();
+        if bytes.len() > usize::from(u8::MAX) {
+            return Err(Error::out_of_range("NVM record length", bytes.len()));
+        }
+        let replaced = self.records.get(&key).map_or(0, |old| 2 + old.len());
+        let used = self.used() - replaced + 2 + bytes.len();
+        if used > Self::CAPACITY {
+            return Err(Error::out_of_range("NVM store size", used));
+        }
+        self.records.insert(key, bytes);
+        Ok(())
+    }
+
+    /// Removes the record with the given key, returning whether there was one.
+    pub fn remove(&mut self, key: u8) -> bool {
+        self.records.remove(&key).is_some()
+    }
+
+    /// Returns the keys of all records, in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = u8> + '_ {
+        self.records.keys().copied()
+    }
+
+    /// Returns the number of bytes of capacity the records use.
+    pub fn used(&self) -> usize {
+        self.records.values().map(|value| 2 + value.len()).sum()
+    }
+}
+
+/// Checks the header at the start of the given contents, returning the length of the
+/// record area which follows it.
+fn record_area_len(contents: &[u8]) -> Result<usize> {
+    if contents.get(..4) != Some(&MAGIC[..]) {
+        return Err(Error::NvmStoreUninitialized);
+    }
+    if contents.len() < HEADER_LEN {
+        return Err(Error::NvmStoreCorrupt("the header is truncated"));
+    }
+    if contents[4] != FORMAT_VERSION {
+        return Err(Error::NvmStoreCorrupt("unknown format version"));
+    }
+    let len = usize::from(u16::from_le_bytes([contents[6], contents[7]]));
+    if len > NvmStore::CAPACITY {
+        return Err(Error::NvmStoreCorrupt("the record area is too long"));
+    }
+    Ok(len)
+}
+
+/// Computes the CRC-16/CCITT-FALSE of the header fields and the records.
+fn crc16(header: &[u8], body: &[u8]) -> u16 {
+    header.iter().chain(body).fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_rejects_a_repeated_key() {
+        let mut store = NvmStore::new(1);
+        store.put(7, &1u8).unwrap();
+        store.put(8, &2u8).unwrap();
+        let mut contents = store.encode();
+        // Give the second record the first one's key, and make the CRC match again.
+        let second = HEADER_LEN + 2 + 1;
+        assert_eq!(contents[second], 8);
+        contents[second] = 7;
+        let crc = crc16(&contents[4..8], &contents[HEADER_LEN..]);
+        contents[8..10].copy_from_slice(&crc.to_le_bytes());
+
+        assert!(matches!(
+            NvmStore::decode(&contents),
+            Err(Error::NvmStoreCorrupt("a key is repeated"))
+        ));
+    }
+}
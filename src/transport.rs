@@ -0,0 +1,101 @@
+//! The byte-stream abstraction over which the RN2903 serial protocol is spoken.
+
+use core::time::Duration;
+use serialport::SerialPort;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+/// The baud rate a zero byte is sent at to hold the line low for a break: at 2400 baud,
+/// the nine low bits last 3.75 ms, many character times at the module's 57600 baud.
+const BREAK_BAUD_RATE: u32 = 2400;
+
+/// A bidirectional byte stream connected to an RN2903 module.
+///
+/// Anything which implements `Read + Write` can carry the RN2903's ASCII protocol; this
+/// trait adds the small amount of link control the `Rn2903` handle needs on top of that.
+/// Implementations are provided for `serialport` trait objects (what
+/// [`Rn2903::new_at()`](struct.Rn2903.html#method.new_at) opens) and for
+/// `std::net::TcpStream`, which is useful for talking to a module behind a TCP serial
+/// bridge.
+///
+/// # Examples
+///
+/// Implementing `Transport` for a custom link which has no notion of a timeout.
+///
+/// ```
+/// # use std::io::{self, Read, Write};
+/// struct Pipe;
+/// # impl Read for Pipe {
+/// #     fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> { Ok(0) }
+/// # }
+/// # impl Write for Pipe {
+/// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+/// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+/// # }
+/// impl rn2903::Transport for Pipe {}
+///
+/// let txvr = rn2903::Rn2903::new_unchecked(Pipe);
+/// ```
+pub trait Transport: Read + Write {
+    /// Sets the amount of time a single `read` may block before giving up.
+    ///
+    /// The default implementation does nothing, which is appropriate for transports
+    /// that never block indefinitely.
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Holds the line low for longer than a character, a break condition, which wakes
+    /// the module from sleep and makes it measure the baud rate again from the next
+    /// byte.
+    ///
+    /// The default implementation fails with `Unsupported`, for transports which cannot
+    /// control the line, like TCP connections.
+    fn send_break(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this transport cannot send a break",
+        ))
+    }
+}
+
+impl Transport for Box<dyn SerialPort> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(&mut **self, timeout).map_err(io::Error::from)
+    }
+
+    /// `serialport` cannot send a break directly, so a zero byte is sent at a much lower
+    /// baud rate instead, which holds the line low for long enough.
+    fn send_break(&mut self) -> io::Result<()> {
+        let baud_rate = self.baud_rate().map_err(io::Error::from)?;
+        self.set_baud_rate(BREAK_BAUD_RATE)
+            .map_err(io::Error::from)?;
+        // Flushing waits until the byte has left the port, before the baud rate changes.
+        let sent = self.write_all(&[0x00]).and_then(|_| self.flush());
+        self.set_baud_rate(baud_rate).map_err(io::Error::from)?;
+        sent
+    }
+}
+
+impl Transport for TcpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        // A zero duration is rejected by `set_read_timeout`, so treat it as the
+        // shortest wait possible instead.
+        let timeout = if timeout == Duration::from_secs(0) {
+            Duration::from_millis(1)
+        } else {
+            timeout
+        };
+        self.set_read_timeout(Some(timeout))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        (**self).set_timeout(timeout)
+    }
+
+    fn send_break(&mut self) -> io::Result<()> {
+        (**self).send_break()
+    }
+}
@@ -0,0 +1,98 @@
+//! The firmware version reported by `sys get ver`.
+
+use core::fmt;
+
+/// The modules this crate can drive, which share a command set but cover different
+/// bands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    /// The RN2903, for the 915 MHz band used in the Americas and Australia.
+    Rn2903,
+    /// The RN2483, for the 433 and 868 MHz bands used in Europe.
+    Rn2483,
+}
+
+impl Model {
+    pub(crate) fn token(self) -> &'static str {
+        match self {
+            Model::Rn2903 => "RN2903",
+            Model::Rn2483 => "RN2483",
+        }
+    }
+
+    pub(crate) fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "RN2903" => Some(Model::Rn2903),
+            "RN2483" => Some(Model::Rn2483),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.token())
+    }
+}
+
+/// A firmware version, as reported by `sys get ver`.
+///
+/// # Example
+///
+/// ```
+/// # use rn2903::{FirmwareVersion, Model};
+/// let version = FirmwareVersion::parse("RN2903 1.0.3 Aug  8 2017 15:11:09").unwrap();
+/// assert_eq!(version.model, Model::Rn2903);
+/// assert_eq!((version.major, version.minor, version.patch), (1, 0, 3));
+/// assert_eq!(version.build_date, "Aug 8 2017 15:11:09");
+///
+/// assert_eq!(FirmwareVersion::parse("RN2"), None);
+/// assert_eq!(FirmwareVersion::parse("RN2903 1.0"), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FirmwareVersion {
+    /// The model of module running the firmware.
+    pub model: Model,
+    /// The major version number.
+    pub major: u8,
+    /// The minor version number.
+    pub minor: u8,
+    /// The patch version number.
+    pub patch: u8,
+    /// The date and time the firmware was built, as the module reports it, like
+    /// `Nov 06 2018 10:45:27`, with runs of spaces collapsed.
+    pub build_date: String,
+}
+
+impl FirmwareVersion {
+    /// Parses the reply to `sys get ver`, returning `None` if it does not name an RN2903
+    /// or RN2483 followed by a `major.minor.patch` version.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut words = version.split_whitespace();
+        let model = Model::from_token(words.next()?)?;
+        let mut numbers = words.next()?.split('.').map(|n| n.parse::<u8>().ok());
+        let major = numbers.next()??;
+        let minor = numbers.next()??;
+        let patch = numbers.next()??;
+        if numbers.next().is_some() {
+            return None;
+        }
+        Some(FirmwareVersion {
+            model,
+            major,
+            minor,
+            patch,
+            build_date: words.collect::<Vec<_>>().join(" "),
+        })
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}.{}.{} {}",
+            self.model, self.major, self.minor, self.patch, self.build_date
+        )
+    }
+}